@@ -0,0 +1,58 @@
+#import bevy_pbr::{
+    forward_io::VertexOutput,
+    mesh_view_bindings::{globals, view},
+}
+
+struct LaserBeam {
+    lock: f32,
+    #ifdef SIXTEEN_BYTE_ALIGNMENT
+        // Web examples WebGL2 support: structs must be 16 byte aligned.
+        _webgl2_padding_8b: u32,
+        _webgl2_padding_12b: u32,
+        _webgl2_padding_16b: u32,
+    #endif
+    color: vec4<f32>,
+}
+
+@group(#{MATERIAL_BIND_GROUP}) @binding(0)
+var<uniform> beam: LaserBeam;
+
+// https://bottosson.github.io/posts/oklab/
+fn oklch_to_linear_srgb(l: f32, c: f32, h: f32) -> vec3<f32> {
+    let a = c * cos(h);
+    let b = c * sin(h);
+
+    let l_ = l + 0.3963377774 * a + 0.2158037573 * b;
+    let m_ = l - 0.1055613458 * a - 0.0638541728 * b;
+    let s_ = l - 0.0894841775 * a - 1.2914855480 * b;
+
+    let l3 = l_ * l_ * l_;
+    let m3 = m_ * m_ * m_;
+    let s3 = s_ * s_ * s_;
+
+    return vec3(
+        4.0767416621 * l3 - 3.3077115913 * m3 + 0.2309699292 * s3,
+        -1.2684380046 * l3 + 2.6097574011 * m3 - 0.3413193965 * s3,
+        -0.0041960863 * l3 - 0.7034186147 * m3 + 1.7076147010 * s3,
+    );
+}
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+) -> @location(0) vec4<f32> {
+    let view_direction = normalize(view.world_position.xyz - in.world_position.xyz);
+    // 0 facing the camera, 1 at the edges of the beam
+    let fresnel = pow(1. - abs(dot(normalize(in.world_normal), view_direction)), 2.);
+
+    // hues cycle along the beam and over time
+    let hue = fresnel * 6.28318 + in.uv.y * 10. - globals.time * 8.;
+    let rainbow = oklch_to_linear_srgb(0.8, 0.25, hue);
+
+    let core = beam.color.rgb * (1. - fresnel) * 4.;
+    let edge = max(rainbow, vec3(0.)) * fresnel * 4.;
+
+    // the tracking indicator is dim until it locks on
+    let strength = mix(0.2, 1., beam.lock * beam.lock);
+    return vec4((core + edge) * strength, 1.);
+}
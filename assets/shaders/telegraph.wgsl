@@ -0,0 +1,65 @@
+#import bevy_pbr::forward_io::VertexOutput
+
+struct Telegraph {
+    progress: f32,
+    param: f32,
+    shape: u32,
+    color: vec4<f32>,
+}
+
+@group(#{MATERIAL_BIND_GROUP}) @binding(0)
+var<uniform> telegraph: Telegraph;
+
+const CIRCLE: u32 = 0u;
+const RING: u32 = 1u;
+const CONE: u32 = 2u;
+const LINE: u32 = 3u;
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+) -> @location(0) vec4<f32> {
+    // -1 to 1 across the quad, with the telegraph's
+    // forward (-z) pointing along -y
+    let p = (in.uv - 0.5) * 2.;
+    let forward = -p.y;
+    let d = length(p);
+
+    // how far along the fill direction this pixel is,
+    // 0 to 1, or negative when outside the shape
+    var along = -1.;
+    switch telegraph.shape {
+        case CIRCLE: {
+            if d <= 1. {
+                along = d;
+            }
+        }
+        case RING: {
+            if d >= telegraph.param && d <= 1. {
+                along = (d - telegraph.param) / (1. - telegraph.param);
+            }
+        }
+        case CONE: {
+            let angle = acos(clamp(forward / max(d, 0.0001), -1., 1.));
+            if d <= 1. && angle <= telegraph.param {
+                along = d;
+            }
+        }
+        case LINE: {
+            if forward >= 0. && abs(p.x) <= telegraph.param {
+                along = forward;
+            }
+        }
+        default: {}
+    }
+    if along < 0. {
+        discard;
+    }
+
+    // a faint area with a solid edge, filling in
+    // from the source as the attack gets closer
+    let edge = smoothstep(0.94, 1., along);
+    let filled = step(along, telegraph.progress);
+    let alpha = max(0.2 + filled * 0.35, edge * 0.8);
+    return vec4(telegraph.color.rgb, telegraph.color.a * alpha);
+}
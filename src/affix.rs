@@ -0,0 +1,331 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use bevy::{color::palettes::tailwind::*, prelude::*};
+use bevy_rand::{global::GlobalRng, prelude::WyRand};
+use rand::{Rng, seq::IndexedRandom};
+
+use crate::{
+    CurrentLevel,
+    archetype::{Archetype, SpawnArchetype},
+    death::Dying,
+    enemy::EnemyBehavior,
+    health::{
+        DamageType, Damaged, Died, Health, Shield, Team,
+    },
+    hitbox::{HitShape, Hitbox, Hurtbox},
+    separation::BodyRadius,
+    spawn_circle::spawn_systems::ScaleIn,
+    telegraph::{GroundTelegraph, TelegraphShape},
+};
+
+/// Chance of an enemy being an elite, per level
+const ELITE_CHANCE_PER_LEVEL: f32 = 0.05;
+const MAX_ELITE_CHANCE: f32 = 0.6;
+/// How many smaller copies a Splitting enemy
+/// breaks into
+const SPLIT_COUNT: usize = 2;
+/// Size of a Splitting enemy's copies, relative
+/// to the original
+const SPLIT_SCALE: f32 = 0.6;
+const EXPLOSION_RADIUS: f32 = 2.;
+const EXPLOSION_DAMAGE: f32 = 15.;
+/// How long an explosion can hit things
+const EXPLOSION_WINDOW: Duration =
+    Duration::from_millis(100);
+/// How long an explosion's flash stays on the
+/// ground
+const EXPLOSION_FLASH: Duration =
+    Duration::from_millis(300);
+/// Share of damage dealt that a Vampiric enemy
+/// heals
+const VAMPIRIC_HEAL: f32 = 0.5;
+
+pub struct AffixPlugin;
+
+/// Elite enemies.
+///
+/// Enemies roll [`Affix`]es from the seeded RNG
+/// when they spawn, with better odds the deeper
+/// the [`CurrentLevel`]. Each affix floats a
+/// colored marker over the enemy's model.
+impl Plugin for AffixPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AffixMarkers>()
+            .add_systems(FixedUpdate, roll_affixes)
+            .add_observer(on_vampiric_damage)
+            .add_observer(on_affixed_death);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Affix {
+    /// starts with a [`Shield`]
+    Shielded,
+    /// moves faster and winds up quicker
+    Hasted,
+    /// breaks into smaller copies on death
+    Splitting,
+    /// heals for part of the damage it deals
+    Vampiric,
+    /// damages nearby players on death
+    Explosive,
+}
+
+impl Affix {
+    const ALL: [Affix; 5] = [
+        Affix::Shielded,
+        Affix::Hasted,
+        Affix::Splitting,
+        Affix::Vampiric,
+        Affix::Explosive,
+    ];
+
+    fn color(self) -> Srgba {
+        match self {
+            Affix::Shielded => SKY_400,
+            Affix::Hasted => YELLOW_400,
+            Affix::Splitting => LIME_400,
+            Affix::Vampiric => RED_600,
+            Affix::Explosive => ORANGE_500,
+        }
+    }
+}
+
+/// The affixes an elite rolled
+#[derive(Component, Debug)]
+pub struct Affixes(pub Vec<Affix>);
+
+impl Affixes {
+    pub fn has(&self, affix: Affix) -> bool {
+        self.0.contains(&affix)
+    }
+}
+
+/// A copy left behind by a Splitting enemy.
+/// Copies don't roll affixes of their own.
+#[derive(Component, Default)]
+pub struct SplitCopy;
+
+#[derive(Resource)]
+struct AffixMarkers {
+    mesh: Handle<Mesh>,
+    materials: Vec<(Affix, Handle<StandardMaterial>)>,
+}
+
+impl FromWorld for AffixMarkers {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Sphere::new(0.08));
+        let mut materials = world
+            .resource_mut::<Assets<StandardMaterial>>();
+        let materials = Affix::ALL
+            .into_iter()
+            .map(|affix| {
+                let color = LinearRgba::from(affix.color());
+                (
+                    affix,
+                    materials.add(StandardMaterial {
+                        base_color: color.into(),
+                        emissive: color * 4.,
+                        unlit: true,
+                        ..default()
+                    }),
+                )
+            })
+            .collect();
+        Self { mesh, materials }
+    }
+}
+
+fn roll_affixes(
+    mut enemies: Query<
+        (Entity, &mut EnemyBehavior, &Health),
+        (Added<EnemyBehavior>, Without<SplitCopy>),
+    >,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    current_level: Res<CurrentLevel>,
+    markers: Res<AffixMarkers>,
+    mut commands: Commands,
+) {
+    let chance = (current_level.0.saturating_sub(1) as f32
+        * ELITE_CHANCE_PER_LEVEL)
+        .min(MAX_ELITE_CHANCE);
+
+    for (entity, mut behavior, health) in &mut enemies {
+        if !rng.random_bool(chance as f64) {
+            continue;
+        }
+        // every elite gets one affix, and sometimes
+        // a second
+        let count = if rng.random_bool(chance as f64 / 2.) {
+            2
+        } else {
+            1
+        };
+        let affixes: Vec<Affix> = Affix::ALL
+            .choose_multiple(&mut **rng, count)
+            .copied()
+            .collect();
+        info!(%entity, ?affixes, "elite spawned");
+
+        for (i, affix) in affixes.iter().enumerate() {
+            match affix {
+                Affix::Shielded => {
+                    commands.entity(entity).insert(
+                        Shield {
+                            current: health.total / 2.,
+                            max: health.total / 2.,
+                        },
+                    );
+                }
+                Affix::Hasted => {
+                    behavior.speed *= 1.5;
+                    behavior.telegraph /= 2;
+                    behavior.cooldown /= 2;
+                }
+                Affix::Splitting
+                | Affix::Vampiric
+                | Affix::Explosive => {}
+            }
+
+            let Some((_, material)) = markers
+                .materials
+                .iter()
+                .find(|(marker, _)| marker == affix)
+            else {
+                continue;
+            };
+            // a row of markers over the model
+            let x = (i as f32
+                - (affixes.len() - 1) as f32 / 2.)
+                * 0.25;
+            commands.spawn((
+                Name::new("AffixMarker"),
+                Mesh3d(markers.mesh.clone()),
+                MeshMaterial3d(material.clone()),
+                Transform::from_xyz(x, 0.9, 0.),
+                ChildOf(entity),
+            ));
+        }
+        commands.entity(entity).insert(Affixes(affixes));
+    }
+}
+
+fn on_vampiric_damage(
+    damaged: On<Damaged>,
+    // the dead don't heal, even off their own
+    // explosion
+    mut attackers: Query<
+        (&Affixes, &mut Health),
+        Without<Dying>,
+    >,
+) {
+    let Ok((affixes, mut health)) =
+        attackers.get_mut(damaged.attacker)
+    else {
+        return;
+    };
+    if !affixes.has(Affix::Vampiric) {
+        return;
+    }
+    health.current = (health.current
+        + damaged.amount * VAMPIRIC_HEAL)
+        .min(health.total);
+}
+
+fn on_affixed_death(
+    died: On<Died>,
+    enemies: Query<(
+        &Affixes,
+        &GlobalTransform,
+        Option<&Archetype>,
+    )>,
+    mut commands: Commands,
+) {
+    let Ok((affixes, transform, archetype)) =
+        enemies.get(died.entity)
+    else {
+        return;
+    };
+    let position = transform.translation();
+
+    if affixes.has(Affix::Explosive) {
+        commands.spawn((
+            Name::new("ExplosionFlash"),
+            GroundTelegraph::new(
+                TelegraphShape::Circle {
+                    radius: EXPLOSION_RADIUS,
+                },
+                EXPLOSION_FLASH,
+                ORANGE_500.with_alpha(0.8),
+            ),
+            Transform::from_translation(
+                position.with_y(0.02),
+            ),
+        ));
+        // credited to the dead enemy, which is
+        // still around for its death sequence
+        commands.spawn((
+            Name::new("Explosion"),
+            Hitbox::new(
+                died.entity,
+                Team::Enemy,
+                HitShape::Circle {
+                    radius: EXPLOSION_RADIUS,
+                },
+                EXPLOSION_DAMAGE,
+                DamageType::Explosive,
+            )
+            .lasting(EXPLOSION_WINDOW),
+            Transform::from_translation(position),
+        ));
+    }
+
+    if let Some(archetype) =
+        archetype.filter(|_| affixes.has(Affix::Splitting))
+    {
+        for i in 0..SPLIT_COUNT {
+            let offset = Vec2::from_angle(
+                TAU * i as f32 / SPLIT_COUNT as f32,
+            ) * 0.5;
+            let spawn = SpawnArchetype {
+                id: archetype.0.clone(),
+                transform: Transform::from_translation(
+                    position + offset.extend(0.).xzy(),
+                ),
+            };
+            commands.queue(move |world: &mut World| {
+                let Some(copy) = spawn.spawn(world) else {
+                    return;
+                };
+                let mut copy = world.entity_mut(copy);
+                // ScaleIn would grow the copy back to
+                // full size
+                copy.insert(SplitCopy).remove::<ScaleIn>();
+                if let Some(mut health) =
+                    copy.get_mut::<Health>()
+                {
+                    *health =
+                        Health::new(health.total / 2.);
+                }
+                if let Some(mut transform) =
+                    copy.get_mut::<Transform>()
+                {
+                    transform.scale =
+                        Vec3::splat(SPLIT_SCALE);
+                }
+                if let Some(mut body) =
+                    copy.get_mut::<BodyRadius>()
+                {
+                    body.0 *= SPLIT_SCALE;
+                }
+                if let Some(mut hurtbox) =
+                    copy.get_mut::<Hurtbox>()
+                {
+                    hurtbox.radius *= SPLIT_SCALE;
+                }
+            });
+        }
+    }
+}
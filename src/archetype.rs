@@ -0,0 +1,406 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::{
+    assets::ArchetypeAssets,
+    awareness::TrackEntities,
+    crystals::{CrystalPylon, EnergyAura},
+    enemy::{
+        CustomLocomotion, EnemyBehavior, EnemyTransitions,
+        Spin, SpinLaser,
+    },
+    eyes::Eyeball,
+    flock_sphere::FlockSphere,
+    health::{DamageType, Health, Resistances, Team},
+    hitbox::Hurtbox,
+    laser::LaserHit,
+    pool::AttachPooledScene,
+    projectile::ProjectileConfig,
+    spawn_circle::{
+        SpawnSystems,
+        spawn_systems::{ScaleIn, TranslateUpIn},
+    },
+    status::{StatusEffect, StatusKind},
+};
+
+pub struct ArchetypePlugin;
+
+/// Enemies defined as data.
+///
+/// Every enemy is described by an
+/// [`EnemyArchetype`] in
+/// `assets/enemies.archetypes.ron`, keyed by its
+/// spawn id. Each id gets a spawn system
+/// registered in [`SpawnSystems`] once the file
+/// loads, and [`SpawnArchetype`] reads the
+/// current definition on every spawn, so edits to
+/// the file apply to the next enemy that spawns.
+impl Plugin for ArchetypePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<EnemyArchetypes>()
+            .init_asset_loader::<EnemyArchetypesLoader>()
+            .add_systems(Update, register_archetype_spawns);
+    }
+}
+
+/// Every archetype, keyed by spawn id
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct EnemyArchetypes {
+    pub archetypes: BTreeMap<String, EnemyArchetype>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct EnemyArchetype {
+    pub name: String,
+    pub kind: ArchetypeKind,
+    /// pooled scene used as the model
+    pub scene: String,
+    pub health: f32,
+    /// how high off the ground the enemy sits
+    #[serde(default)]
+    pub spawn_height: f32,
+    #[serde(default)]
+    pub tracking: Option<TrackingDef>,
+    /// radius of the [`Hurtbox`], if it isn't the
+    /// default
+    #[serde(default)]
+    pub hurtbox: Option<f32>,
+    /// damage multipliers, see [`Resistances`]
+    #[serde(default)]
+    pub resistances: BTreeMap<DamageType, f32>,
+    /// enemies without a behavior stand still
+    #[serde(default)]
+    pub behavior: Option<BehaviorDef>,
+}
+
+/// Components an archetype's code still provides
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub enum ArchetypeKind {
+    Eye,
+    FlockSphere,
+    CrystalPylon,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TrackingDef {
+    pub in_range: f32,
+    pub max_range: f32,
+}
+
+/// An [`EnemyBehavior`], with durations in
+/// seconds
+#[derive(Deserialize, Clone, Debug)]
+pub struct BehaviorDef {
+    pub speed: f32,
+    pub attack_range: f32,
+    #[serde(default)]
+    pub transitions: EnemyTransitions,
+    #[serde(default)]
+    pub telegraph: f32,
+    #[serde(default)]
+    pub cooldown: f32,
+    pub laser: LaserDef,
+    #[serde(default)]
+    pub projectile: Option<ProjectileDef>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LaserDef {
+    pub duration: f32,
+    pub length: f32,
+    pub width: f32,
+    pub damage: f32,
+    /// seconds before the laser can hit the same
+    /// target again
+    pub cooldown: f32,
+    pub spin: Spin,
+    #[serde(default)]
+    pub face_target: bool,
+}
+
+/// A [`ProjectileConfig`]. Anything left out uses
+/// the config's defaults.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ProjectileDef {
+    pub speed: f32,
+    pub damage: f32,
+    #[serde(default)]
+    pub damage_type: Option<DamageType>,
+    #[serde(default)]
+    pub lifetime: Option<f32>,
+    #[serde(default)]
+    pub homing: Option<f32>,
+    #[serde(default)]
+    pub radius: Option<f32>,
+    #[serde(default)]
+    pub arc: Option<f32>,
+    /// hex color, like `"#38bdf8"`
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub effects: Vec<StatusDef>,
+}
+
+/// A [`StatusEffect`], with its duration in
+/// seconds
+#[derive(Deserialize, Clone, Debug)]
+pub struct StatusDef {
+    pub kind: StatusKind,
+    #[serde(default)]
+    pub magnitude: f32,
+    pub duration: f32,
+}
+
+impl StatusDef {
+    fn effect(&self) -> StatusEffect {
+        StatusEffect::new(
+            self.kind,
+            self.magnitude,
+            Duration::from_secs_f32(self.duration),
+        )
+    }
+}
+
+impl BehaviorDef {
+    fn behavior(&self) -> EnemyBehavior {
+        EnemyBehavior {
+            speed: self.speed,
+            attack_range: self.attack_range,
+            transitions: self.transitions.clone(),
+            telegraph: Duration::from_secs_f32(
+                self.telegraph,
+            ),
+            cooldown: Duration::from_secs_f32(
+                self.cooldown,
+            ),
+            laser: SpinLaser {
+                duration: Duration::from_secs_f32(
+                    self.laser.duration,
+                ),
+                hit: LaserHit::new(
+                    self.laser.length,
+                    self.laser.width,
+                    self.laser.damage,
+                    Duration::from_secs_f32(
+                        self.laser.cooldown,
+                    ),
+                ),
+                spin: self.laser.spin,
+                face_target: self.laser.face_target,
+            },
+            projectile: self
+                .projectile
+                .as_ref()
+                .map(ProjectileDef::config),
+        }
+    }
+}
+
+impl ProjectileDef {
+    fn config(&self) -> ProjectileConfig {
+        let default = ProjectileConfig::default();
+        ProjectileConfig {
+            speed: self.speed,
+            lifetime: self
+                .lifetime
+                .map(Duration::from_secs_f32)
+                .unwrap_or(default.lifetime),
+            homing: self.homing.unwrap_or(default.homing),
+            damage: self.damage,
+            damage_type: self
+                .damage_type
+                .unwrap_or(default.damage_type),
+            effects: self
+                .effects
+                .iter()
+                .map(StatusDef::effect)
+                .collect(),
+            radius: self.radius.unwrap_or(default.radius),
+            arc: self.arc,
+            color: self
+                .color
+                .as_deref()
+                .and_then(|color| Srgba::hex(color).ok())
+                .map(LinearRgba::from)
+                .unwrap_or(default.color),
+        }
+    }
+}
+
+#[derive(Default, TypePath)]
+struct EnemyArchetypesLoader;
+
+impl AssetLoader for EnemyArchetypesLoader {
+    type Asset = EnemyArchetypes;
+    type Settings = ();
+    type Error = BevyError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<EnemyArchetypes, BevyError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["archetypes.ron"]
+    }
+}
+
+/// Register a spawn system for every archetype
+/// that doesn't have one yet, including ones
+/// added to the file while the game is running
+fn register_archetype_spawns(
+    mut events: MessageReader<AssetEvent<EnemyArchetypes>>,
+    archetypes: Res<Assets<EnemyArchetypes>>,
+    spawn_systems: Res<SpawnSystems>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let (AssetEvent::LoadedWithDependencies { id }
+        | AssetEvent::Modified { id }) = event
+        else {
+            continue;
+        };
+        let Some(archetypes) = archetypes.get(*id) else {
+            continue;
+        };
+        for id in archetypes.archetypes.keys() {
+            if spawn_systems.0.contains_key(id) {
+                continue;
+            }
+            let id = id.clone();
+            commands.queue(move |world: &mut World| {
+                let spawn_id = id.clone();
+                let system = world.register_system(
+                    move |transform: In<Transform>,
+                          mut commands: Commands| {
+                        commands.queue(SpawnArchetype {
+                            id: spawn_id.clone(),
+                            transform: *transform,
+                        });
+                    },
+                );
+                world
+                    .resource_mut::<SpawnSystems>()
+                    .0
+                    .insert(id, system);
+            });
+        }
+    }
+}
+
+/// The spawn id an enemy was spawned from
+#[derive(Component, Clone, Debug)]
+pub struct Archetype(pub String);
+
+/// Spawn the enemy archetype registered as `id`
+pub struct SpawnArchetype {
+    pub id: String,
+    pub transform: Transform,
+}
+
+impl Command for SpawnArchetype {
+    fn apply(self, world: &mut World) {
+        self.spawn(world);
+    }
+}
+
+impl SpawnArchetype {
+    /// Spawn now, returning the new enemy
+    pub fn spawn(
+        self,
+        world: &mut World,
+    ) -> Option<Entity> {
+        let handle = world
+            .resource::<ArchetypeAssets>()
+            .enemies
+            .clone();
+        let Some(archetype) = world
+            .resource::<Assets<EnemyArchetypes>>()
+            .get(&handle)
+            .and_then(|archetypes| {
+                archetypes.archetypes.get(&self.id)
+            })
+            .cloned()
+        else {
+            warn!(id = %self.id, "no enemy archetype to spawn");
+            return None;
+        };
+
+        let mut transform = self.transform;
+        transform.translation.y = archetype.spawn_height;
+
+        let mut enemy = world.spawn((
+            Name::new(archetype.name),
+            Archetype(self.id),
+            Team::Enemy,
+            Health::new(archetype.health),
+            archetype
+                .hurtbox
+                .map(|radius| Hurtbox { radius })
+                .unwrap_or_default(),
+            Visibility::default(),
+            transform,
+            ScaleIn(Timer::new(
+                Duration::from_millis(100),
+                TimerMode::Once,
+            )),
+            TranslateUpIn {
+                timer: Timer::new(
+                    Duration::from_millis(250),
+                    TimerMode::Once,
+                ),
+                target: transform.translation,
+            },
+        ));
+        match archetype.kind {
+            ArchetypeKind::Eye => {
+                enemy.insert(Eyeball);
+            }
+            ArchetypeKind::FlockSphere => {
+                enemy.insert((
+                    FlockSphere,
+                    CustomLocomotion,
+                ));
+            }
+            ArchetypeKind::CrystalPylon => {
+                enemy.insert((
+                    CrystalPylon,
+                    EnergyAura::default(),
+                ));
+            }
+        }
+        if let Some(tracking) = &archetype.tracking {
+            enemy.insert(TrackEntities::new(
+                tracking.in_range,
+                tracking.max_range,
+            ));
+        }
+        if let Some(behavior) = &archetype.behavior {
+            enemy.insert(behavior.behavior());
+        }
+        if !archetype.resistances.is_empty() {
+            enemy.insert(Resistances(
+                archetype.resistances.clone(),
+            ));
+        }
+        let parent = enemy.id();
+
+        AttachPooledScene {
+            parent,
+            scene: archetype.scene,
+        }
+        .apply(world);
+        Some(parent)
+    }
+}
@@ -10,6 +10,8 @@ use iyes_progress::{
     ProgressTracker,
 };
 
+use crate::archetype::EnemyArchetypes;
+
 // Time in seconds to complete a custom
 // long-running task. If assets are loaded
 // earlier, the current state will not be changed
@@ -34,7 +36,8 @@ impl Plugin for JamAssetsPlugin {
             .add_loading_state(
                 LoadingState::new(MyStates::AssetLoading)
                     .load_collection::<GltfAssets>()
-                    .load_collection::<ImageAssets>(),
+                    .load_collection::<ImageAssets>()
+                    .load_collection::<ArchetypeAssets>(),
             )
             .add_systems(
                 Update,
@@ -70,6 +73,12 @@ pub struct GltfAssets {
     pub misc: Handle<Gltf>,
 }
 
+#[derive(AssetCollection, Resource)]
+pub struct ArchetypeAssets {
+    #[asset(path = "enemies.archetypes.ron")]
+    pub enemies: Handle<EnemyArchetypes>,
+}
+
 #[derive(AssetCollection, Resource)]
 struct ImageAssets {
     #[asset(path = "001/eyeball_BaseColor.png")]
@@ -3,13 +3,25 @@ use bevy::{
     ecs::entity::EntityHashSet, prelude::*,
 };
 
+use crate::line_of_sight::LineOfSight;
+
 pub struct AwarenessPlugin;
 
-/// How entities detect other entities
+/// How entities detect other entities.
+///
+/// A [`Detectable`] entity is detected once it
+/// comes within `in_range` of a [`TrackEntities`]
+/// entity, and only lost once it leaves
+/// `max_range`, so entities hovering around the
+/// edge don't flicker in and out. Both need
+/// [`LineOfSight`], so ducking behind a wall
+/// loses whoever is tracking you.
 impl Plugin for AwarenessPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(FixedUpdate, detect_in_range)
-            .add_systems(FixedUpdate, detect_out_of_range);
+        app.add_systems(
+            FixedUpdate,
+            (detect_out_of_range, detect_in_range).chain(),
+        );
     }
 }
 
@@ -17,54 +29,132 @@ impl Plugin for AwarenessPlugin {
 #[reflect(Component)]
 #[type_path = "api"]
 #[require(DetectedEntities)]
-struct TrackEntities {
-    in_range: Circle,
-    max_range: Circle,
+pub struct TrackEntities {
+    pub in_range: Circle,
+    pub max_range: Circle,
 }
 
+impl TrackEntities {
+    pub fn new(in_range: f32, max_range: f32) -> Self {
+        Self {
+            in_range: Circle::new(in_range),
+            max_range: Circle::new(max_range),
+        }
+    }
+}
+
+/// Everything a [`TrackEntities`] entity
+/// currently knows about
 #[derive(Component, Default)]
-struct DetectedEntities(EntityHashSet);
+pub struct DetectedEntities(pub EntityHashSet);
 
 /// An entity that can be detected by this module
 /// if it is in range of another entity
-#[derive(Component, Reflect)]
+#[derive(Component, Reflect, Default)]
 #[reflect(Component)]
 #[type_path = "api"]
 pub struct Detectable;
 
+/// Triggered on a [`TrackEntities`] entity when
+/// it detects `target`
+#[derive(EntityEvent)]
+pub struct Detected {
+    pub entity: Entity,
+    pub target: Entity,
+}
+
+/// Triggered on a [`TrackEntities`] entity when
+/// `target` leaves `max_range` or is despawned
+#[derive(EntityEvent)]
+pub struct Lost {
+    pub entity: Entity,
+    pub target: Entity,
+}
+
 fn detect_in_range(
-    _entities: Query<&GlobalTransform, With<Detectable>>,
+    entities: Query<
+        (Entity, &GlobalTransform),
+        With<Detectable>,
+    >,
     mut query: Query<(
+        Entity,
         &TrackEntities,
         &GlobalTransform,
         &mut DetectedEntities,
     )>,
     mut gizmos: Gizmos,
+    mut commands: Commands,
+    line_of_sight: LineOfSight,
 ) {
-    for (tracking_info, transform, _detected) in &mut query
+    for (entity, tracking_info, transform, mut detected) in
+        &mut query
     {
         gizmos.circle(
             transform.translation(),
             tracking_info.in_range.radius,
             RED_400,
         );
+
+        let position = transform.translation().xz();
+        for (target, target_transform) in &entities {
+            if target == entity
+                || detected.0.contains(&target)
+            {
+                continue;
+            }
+            let target_position =
+                target_transform.translation().xz();
+            if target_position.distance(position)
+                <= tracking_info.in_range.radius
+                && line_of_sight
+                    .clear(position, target_position)
+            {
+                detected.0.insert(target);
+                commands
+                    .trigger(Detected { entity, target });
+            }
+        }
     }
 }
+
 fn detect_out_of_range(
-    _entities: Query<&GlobalTransform, With<Detectable>>,
+    entities: Query<&GlobalTransform, With<Detectable>>,
     mut query: Query<(
+        Entity,
         &TrackEntities,
         &GlobalTransform,
         &mut DetectedEntities,
     )>,
     mut gizmos: Gizmos,
+    mut commands: Commands,
+    line_of_sight: LineOfSight,
 ) {
-    for (tracking_info, transform, _detected) in &mut query
+    for (entity, tracking_info, transform, mut detected) in
+        &mut query
     {
         gizmos.circle(
             transform.translation(),
             tracking_info.max_range.radius,
             GREEN_400,
         );
+
+        let position = transform.translation().xz();
+        detected.0.retain(|target| {
+            let in_range =
+                entities.get(*target).is_ok_and(|target| {
+                    let target = target.translation().xz();
+                    target.distance(position)
+                        <= tracking_info.max_range.radius
+                        && line_of_sight
+                            .clear(position, target)
+                });
+            if !in_range {
+                commands.trigger(Lost {
+                    entity,
+                    target: *target,
+                });
+            }
+            in_range
+        });
     }
 }
@@ -0,0 +1,563 @@
+use std::{
+    f32::consts::{FRAC_PI_2, TAU},
+    time::Duration,
+};
+
+use bevy::{
+    color::palettes::tailwind::*,
+    light::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+};
+
+use crate::{
+    health::{Health, Team},
+    hitbox::Hurtbox,
+    laser::{LaserConfig, RunningLaserProcess},
+    navmesh::CurrentNavMesh,
+    player::PlayerCharacter,
+    pool::AttachPooledScene,
+    separation::BodyRadius,
+    spawn_circle::{
+        InitSpawnCircle, SpawnSystems,
+        spawn_queue::{SpawnPriority, SpawnQueue},
+        spawn_systems::{
+            AppSpawnExt, ScaleIn, TranslateUpIn,
+        },
+    },
+    status::StatusEffects,
+};
+
+pub struct BossPlugin;
+
+/// Bosses for milestone levels.
+///
+/// A [`Boss`] fires [`RunningLaserProcess`]es at
+/// the player and summons waves of enemies
+/// through spawn circles. Its [`BossPhase`] moves
+/// on at 66% and 33% health, which speeds up and
+/// hardens its attacks. From the moment a boss
+/// spawns until it dies, the player is held
+/// inside its [`ArenaLock`], the ring drawn on
+/// the ground.
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_spawn_system(
+            "boss".to_string(),
+            one_shot_spawn_boss,
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                update_boss_phase,
+                boss_attacks,
+                hold_arena,
+            )
+                .chain(),
+        )
+        .add_systems(Update, sync_boss_health_bar)
+        .add_observer(on_add_boss)
+        .add_observer(on_remove_boss)
+        .add_observer(on_add_arena_lock)
+        .add_observer(on_remove_arena_lock);
+    }
+}
+
+/// Every `BOSS_EVERY`th level ends with a boss
+pub const BOSS_EVERY: u32 = 3;
+
+/// How far from the boss summoned waves spawn
+const SUMMON_RADIUS: f32 = 4.;
+
+/// Width of the ring marking an [`ArenaLock`]'s
+/// edge
+const ARENA_RING_WIDTH: f32 = 0.1;
+
+pub fn is_boss_level(level: u32) -> bool {
+    level > 0 && level.is_multiple_of(BOSS_EVERY)
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+pub enum BossPhase {
+    #[default]
+    One,
+    Two,
+    Three,
+}
+
+impl BossPhase {
+    /// The phase for a fraction of total health
+    pub fn for_health(fraction: f32) -> Self {
+        if fraction > 0.66 {
+            BossPhase::One
+        } else if fraction > 0.33 {
+            BossPhase::Two
+        } else {
+            BossPhase::Three
+        }
+    }
+
+    fn laser(self) -> LaserConfig {
+        match self {
+            BossPhase::One => LaserConfig {
+                length: 10.,
+                width: 0.4,
+                damage_per_tick: 6.,
+                color: PURPLE_400.into(),
+                ..default()
+            },
+            BossPhase::Two => LaserConfig {
+                lock_on: Duration::from_secs(1),
+                fire: Duration::from_millis(1500),
+                length: 12.,
+                width: 0.5,
+                damage_per_tick: 8.,
+                color: FUCHSIA_400.into(),
+                ..default()
+            },
+            BossPhase::Three => LaserConfig {
+                lock_on: Duration::from_millis(600),
+                fire: Duration::from_secs(2),
+                length: 14.,
+                width: 0.7,
+                damage_per_tick: 10.,
+                color: ROSE_400.into(),
+                ..default()
+            },
+        }
+    }
+
+    /// Time between lasers
+    fn attack_interval(self) -> Duration {
+        match self {
+            BossPhase::One => Duration::from_secs(4),
+            BossPhase::Two => Duration::from_secs(3),
+            BossPhase::Three => Duration::from_secs(2),
+        }
+    }
+
+    /// Enemies summoned on entering the phase
+    fn wave(self) -> &'static [&'static str] {
+        match self {
+            BossPhase::One => &[],
+            BossPhase::Two => &[
+                "flock-sphere",
+                "flock-sphere",
+                "flock-sphere",
+                "flock-sphere",
+            ],
+            BossPhase::Three => &[
+                "eye",
+                "flock-sphere",
+                "eye",
+                "flock-sphere",
+            ],
+        }
+    }
+}
+
+#[derive(Component)]
+#[require(
+    Team = Team::Enemy,
+    BodyRadius = BodyRadius(1.5),
+    Hurtbox = Hurtbox { radius: 1.5 }
+)]
+pub struct Boss {
+    phase: BossPhase,
+    attack_timer: Timer,
+}
+
+impl Default for Boss {
+    fn default() -> Self {
+        Self {
+            phase: BossPhase::One,
+            attack_timer: Timer::new(
+                BossPhase::One.attack_interval(),
+                TimerMode::Repeating,
+            ),
+        }
+    }
+}
+
+impl Boss {
+    pub fn phase(&self) -> BossPhase {
+        self.phase
+    }
+}
+
+/// Triggered on a [`Boss`] when its health drops
+/// past a phase threshold
+#[derive(EntityEvent)]
+pub struct BossPhaseChanged {
+    pub entity: Entity,
+    pub phase: BossPhase,
+}
+
+/// Keeps the player within `radius` of `center`
+/// for as long as this entity is alive. A player
+/// outside when the lock shows up is pulled to
+/// its edge.
+#[derive(Component, Debug)]
+pub struct ArenaLock {
+    pub center: Vec2,
+    pub radius: f32,
+}
+
+impl ArenaLock {
+    pub fn new(center: Vec2, radius: f32) -> Self {
+        Self { center, radius }
+    }
+}
+
+/// The ring on the ground marking an
+/// [`ArenaLock`]'s edge
+#[derive(Component)]
+struct ArenaRing {
+    lock: Entity,
+}
+
+/// The screen-space health bar for a boss
+#[derive(Component)]
+struct BossHealthBar {
+    boss: Entity,
+}
+
+#[derive(Component)]
+struct BossHealthFill;
+
+fn one_shot_spawn_boss(
+    mut transform: In<Transform>,
+    mut commands: Commands,
+) {
+    transform.translation.y = 1.5;
+
+    let boss = commands
+        .spawn((
+            Name::new("Boss"),
+            Boss::default(),
+            Health::new(600.),
+            ArenaLock::new(transform.translation.xz(), 10.),
+            Visibility::default(),
+            *transform,
+            ScaleIn(Timer::new(
+                Duration::from_millis(400),
+                TimerMode::Once,
+            )),
+            TranslateUpIn {
+                timer: Timer::new(
+                    Duration::from_millis(800),
+                    TimerMode::Once,
+                ),
+                target: transform.translation,
+            },
+        ))
+        .id();
+
+    // ScaleIn owns the root's scale, so the model
+    // is scaled up on a child
+    let model = commands
+        .spawn((
+            Name::new("BossModel"),
+            Transform::from_scale(Vec3::splat(3.)),
+            Visibility::default(),
+            ChildOf(boss),
+        ))
+        .id();
+    commands.queue(AttachPooledScene {
+        parent: model,
+        scene: "Eye".to_string(),
+    });
+}
+
+fn update_boss_phase(
+    mut bosses: Query<(
+        Entity,
+        &mut Boss,
+        &Health,
+        &StatusEffects,
+        &GlobalTransform,
+    )>,
+    spawn_systems: Res<SpawnSystems>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+    navmesh: CurrentNavMesh,
+    mut commands: Commands,
+) {
+    for (entity, mut boss, health, statuses, transform) in
+        &mut bosses
+    {
+        let phase = BossPhase::for_health(
+            health.current / health.total,
+        );
+        // phases only move forward, so healing a
+        // boss doesn't replay its summons. A stunned
+        // boss changes phase once it recovers.
+        if phase <= boss.phase || !statuses.can_attack() {
+            continue;
+        }
+        info!(?phase, "boss entered a new phase");
+        boss.phase = phase;
+        boss.attack_timer = Timer::new(
+            phase.attack_interval(),
+            TimerMode::Repeating,
+        );
+        commands
+            .trigger(BossPhaseChanged { entity, phase });
+
+        let center = transform.translation().xz();
+        let wave = phase.wave();
+        for (i, enemy) in wave.iter().enumerate() {
+            let angle = TAU * i as f32 / wave.len() as f32;
+            let position = center
+                + Vec2::from_angle(angle) * SUMMON_RADIUS;
+            if !navmesh.contains(position) {
+                continue;
+            }
+            let Some(id) = spawn_systems.0.get(*enemy)
+            else {
+                warn!(
+                    enemy,
+                    "boss can't summon unregistered enemy"
+                );
+                continue;
+            };
+            spawn_queue.push(
+                SpawnPriority::High,
+                InitSpawnCircle {
+                    position,
+                    event: *id,
+                    spawn_color: PURPLE_400.into(),
+                },
+            );
+        }
+    }
+}
+
+fn boss_attacks(
+    mut bosses: Query<
+        (
+            Entity,
+            &mut Boss,
+            &mut Transform,
+            &StatusEffects,
+            Has<RunningLaserProcess>,
+        ),
+        (Without<ScaleIn>, Without<TranslateUpIn>),
+    >,
+    player: Query<
+        (Entity, &GlobalTransform),
+        With<PlayerCharacter>,
+    >,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    let Ok((player, player_transform)) = player.single()
+    else {
+        return;
+    };
+    for (
+        entity,
+        mut boss,
+        mut transform,
+        statuses,
+        firing,
+    ) in &mut bosses
+    {
+        // a stun cuts off the laser and holds the
+        // next one
+        if !statuses.can_attack() {
+            if firing {
+                commands
+                    .entity(entity)
+                    .remove::<RunningLaserProcess>();
+            }
+            continue;
+        }
+        let target = transform.looking_at(
+            player_transform
+                .translation()
+                .with_y(transform.translation.y),
+            Vec3::Y,
+        );
+        transform.rotation.smooth_nudge(
+            &target.rotation,
+            2.,
+            time.delta_secs(),
+        );
+
+        // the next attack waits for the current
+        // laser to finish
+        if firing
+            || !boss
+                .attack_timer
+                .tick(time.delta())
+                .just_finished()
+        {
+            continue;
+        }
+        commands.entity(entity).insert(
+            RunningLaserProcess::new(
+                player,
+                boss.phase.laser(),
+            ),
+        );
+    }
+}
+
+fn hold_arena(
+    locks: Query<&ArenaLock>,
+    mut players: Query<
+        &mut Transform,
+        With<PlayerCharacter>,
+    >,
+) {
+    for lock in &locks {
+        for mut transform in &mut players {
+            let offset =
+                transform.translation.xz() - lock.center;
+            if offset.length() <= lock.radius {
+                continue;
+            }
+            let held = lock.center
+                + offset.clamp_length_max(lock.radius);
+            transform.translation.x = held.x;
+            transform.translation.z = held.y;
+        }
+    }
+}
+
+fn on_add_arena_lock(
+    added: On<Add, ArenaLock>,
+    locks: Query<&ArenaLock>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut commands: Commands,
+) {
+    let Ok(lock) = locks.get(added.entity) else {
+        return;
+    };
+    let color = LinearRgba::from(PURPLE_400);
+    commands.spawn((
+        Name::new("ArenaRing"),
+        ArenaRing { lock: added.entity },
+        Mesh3d(meshes.add(Annulus::new(
+            lock.radius - ARENA_RING_WIDTH,
+            lock.radius,
+        ))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: color.into(),
+            emissive: color * 4.,
+            unlit: true,
+            ..default()
+        })),
+        Transform::from_translation(
+            lock.center.extend(0.05).xzy(),
+        )
+        .with_rotation(Quat::from_rotation_x(-FRAC_PI_2)),
+        NotShadowCaster,
+        NotShadowReceiver,
+    ));
+}
+
+fn on_remove_arena_lock(
+    removed: On<Remove, ArenaLock>,
+    rings: Query<(Entity, &ArenaRing)>,
+    mut commands: Commands,
+) {
+    for (entity, ring) in &rings {
+        if ring.lock == removed.entity {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn on_add_boss(
+    added: On<Add, Boss>,
+    mut commands: Commands,
+) {
+    commands.spawn((
+        Name::new("BossHealthBar"),
+        BossHealthBar { boss: added.entity },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(16.),
+            left: Val::Percent(20.),
+            width: Val::Percent(60.),
+            height: Val::Px(18.),
+            padding: UiRect::all(Val::Px(3.)),
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.6)),
+        children![(
+            BossHealthFill,
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                ..default()
+            },
+            BackgroundColor(PURPLE_400.into()),
+        )],
+    ));
+}
+
+fn on_remove_boss(
+    removed: On<Remove, Boss>,
+    bars: Query<(Entity, &BossHealthBar)>,
+    mut commands: Commands,
+) {
+    for (entity, bar) in &bars {
+        if bar.boss == removed.entity {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn sync_boss_health_bar(
+    bars: Query<(&BossHealthBar, &Children)>,
+    mut fills: Query<&mut Node, With<BossHealthFill>>,
+    bosses: Query<&Health, With<Boss>>,
+) {
+    for (bar, children) in &bars {
+        let Ok(health) = bosses.get(bar.boss) else {
+            continue;
+        };
+        let fraction =
+            (health.current / health.total).clamp(0., 1.);
+        for child in children.iter() {
+            if let Ok(mut node) = fills.get_mut(child) {
+                node.width = Val::Percent(fraction * 100.);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phases_change_at_two_thirds_and_one_third() {
+        for (fraction, phase) in [
+            (1., BossPhase::One),
+            (0.67, BossPhase::One),
+            (0.66, BossPhase::Two),
+            (0.34, BossPhase::Two),
+            (0.33, BossPhase::Three),
+            (0., BossPhase::Three),
+            (-0.5, BossPhase::Three),
+        ] {
+            assert_eq!(
+                BossPhase::for_health(fraction),
+                phase,
+                "at {fraction}"
+            );
+        }
+    }
+}
@@ -3,7 +3,9 @@ use std::f32::consts::FRAC_PI_4;
 use bevy::prelude::*;
 use bevy_enhanced_input::prelude::*;
 
-use crate::animation_extension::Animations;
+use crate::{
+    animation_extension::Animations, status::StatusEffects,
+};
 
 pub struct ControlsPlugin;
 
@@ -34,12 +36,18 @@ pub struct ControlledByPlayer;
 fn apply_movement(
     movement: On<Fire<Movement>>,
     mut transforms: Query<
-        &mut Transform,
+        (&mut Transform, Option<&StatusEffects>),
         With<ControlledByPlayer>,
     >,
 ) {
-    let mut transform =
+    let (mut transform, statuses) =
         transforms.get_mut(movement.context).unwrap();
+    let speed = statuses
+        .map_or(1., StatusEffects::speed_multiplier);
+    // stunned players can't move or turn
+    if speed <= 0. {
+        return;
+    }
 
     // rotation is -45deg so that it goes "north", as
     // per the camera TODO: is camera rotation
@@ -49,7 +57,8 @@ fn apply_movement(
     let mut velocity = movement.value.extend(0.0).xzy();
     velocity.z = -velocity.z;
 
-    let distance_to_move = rotation * velocity * 0.06;
+    let distance_to_move =
+        rotation * velocity * 0.06 * speed;
 
     transform.translation += distance_to_move;
     transform.rotation = Quat::from_rotation_y(
@@ -93,8 +102,15 @@ fn on_hammer_slam(
     >,
     // hack for animation-having player
     animations: Single<(&mut AnimationPlayer, &Animations)>,
+    statuses: Query<&StatusEffects>,
 ) {
     info!(?slam);
+    if statuses
+        .get(slam.context)
+        .is_ok_and(|statuses| !statuses.can_attack())
+    {
+        return;
+    }
     let (mut player, animations) = animations.into_inner();
     player.stop_all();
 
@@ -1,6 +1,8 @@
 use std::{f32::consts::FRAC_PI_2, time::Duration};
 
 use bevy::{
+    color::palettes::tailwind::CYAN_400,
+    light::{NotShadowCaster, NotShadowReceiver},
     pbr::{ExtendedMaterial, MaterialExtension},
     prelude::*,
     render::render_resource::AsBindGroup,
@@ -9,22 +11,23 @@ use bevy::{
 use noiz::prelude::*;
 
 use crate::{
-    assets::GltfAssets,
-    health::Health,
-    spawn_circle::spawn_systems::{
-        AppSpawnExt, ScaleIn, TranslateUpIn,
-    },
+    death::Dying,
+    health::{Health, Shield, Team},
+    level::Persistent,
+    separation::{BodyRadius, Immovable},
+    status::{StatusApplied, StatusEffect, StatusEffects},
 };
 
+/// How close to running out an aura's status
+/// effect gets before it's applied again
+const AURA_STATUS_REFRESH: Duration =
+    Duration::from_millis(250);
+
 pub struct CrystalPlugin;
 
 impl Plugin for CrystalPlugin {
     fn build(&self, app: &mut App) {
-        app.register_spawn_system(
-            "gem-rock".to_string(),
-            one_shot_spawn_gem_rock,
-        )
-        .add_plugins((
+        app.add_plugins((
             MaterialPlugin::<
                 ExtendedMaterial<
                     StandardMaterial,
@@ -47,7 +50,13 @@ impl Plugin for CrystalPlugin {
         .add_observer(on_add_crystal_material)
         .add_observer(on_add_energy_material)
         .add_observer(on_add_energy_well_material)
-        .add_systems(FixedUpdate, rotate_material);
+        .init_resource::<EnergyLinks>()
+        .add_observer(on_add_energy_aura)
+        .add_systems(
+            FixedUpdate,
+            (rotate_material, project_energy_auras),
+        )
+        .add_systems(Update, draw_energy_links);
     }
 }
 
@@ -259,38 +268,271 @@ impl MaterialExtension for EnergyWellExt {
 }
 
 #[derive(Component)]
+#[require(BodyRadius, Immovable)]
 pub struct CrystalPylon;
 
-fn one_shot_spawn_gem_rock(
-    transform: In<Transform>,
+/// Empowers enemies within `radius`, and draws an
+/// energy link to each one so players know which
+/// pylon to take out first
+#[derive(Component, Clone, Debug)]
+pub struct EnergyAura {
+    pub radius: f32,
+    pub effect: AuraEffect,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum AuraEffect {
+    /// health per second
+    Heal(f32),
+    /// shield per second, up to `max`
+    Shield { rate: f32, max: f32 },
+    /// reapplied as it's about to run out, so it
+    /// lasts as long as the enemy stays in range
+    /// plus its duration
+    Status(StatusEffect),
+}
+
+impl Default for EnergyAura {
+    fn default() -> Self {
+        Self {
+            radius: 4.,
+            effect: AuraEffect::Heal(4.),
+        }
+    }
+}
+
+/// The energy well on the ground showing an
+/// [`EnergyAura`]'s reach
+fn on_add_energy_aura(
+    added: On<Add, EnergyAura>,
+    auras: Query<&EnergyAura>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<
+        Assets<
+            ExtendedMaterial<
+                StandardMaterial,
+                EnergyWellExt,
+            >,
+        >,
+    >,
     mut commands: Commands,
-    gltf: ResMut<GltfAssets>,
-    gltfs: Res<Assets<Gltf>>,
+    time: Res<Time>,
 ) {
-    commands.spawn(
-        (
-            Name::new("CrystalPylon"),
-            CrystalPylon,
-            SceneRoot(
-                gltfs.get(&gltf.misc).unwrap().named_scenes
-                    ["gem-rock"]
-                    .clone(),
-            ),
-            *transform,
-            Health::new(200.),
-            ScaleIn(Timer::new(
-                Duration::from_millis(100),
-                TimerMode::Once,
-            )),
-            TranslateUpIn {
-                timer: Timer::new(
-                    Duration::from_millis(250),
-                    TimerMode::Once,
+    let Ok(aura) = auras.get(added.entity) else {
+        return;
+    };
+    commands.spawn((
+        Name::new("EnergyWell"),
+        Mesh3d(meshes.add(Circle::new(aura.radius))),
+        MeshMaterial3d(materials.add(ExtendedMaterial {
+            base: StandardMaterial {
+                // the energy well shader brightens
+                // this a lot
+                base_color: Color::linear_rgb(
+                    0.0002, 0.0008, 0.001,
                 ),
-                target: transform.translation,
+                unlit: true,
+                ..default()
+            },
+            extension: EnergyWellExt {
+                spawn_time: time.elapsed_secs(),
+                ..default()
             },
+        })),
+        Transform::from_xyz(0., 0.02, 0.).with_rotation(
+            Quat::from_rotation_x(-FRAC_PI_2),
         ),
-    );
+        NotShadowCaster,
+        NotShadowReceiver,
+        ChildOf(added.entity),
+    ));
+}
+
+/// The links from pylons to the enemies they
+/// empower, drawn with a pool of [`EnergyLink`]
+/// beams that all share one mesh and material
+#[derive(Resource)]
+struct EnergyLinks {
+    mesh: Handle<Mesh>,
+    material: Handle<
+        ExtendedMaterial<StandardMaterial, EnergyExt>,
+    >,
+    /// from and to for every link this tick
+    segments: Vec<(Vec3, Vec3)>,
+}
+
+impl FromWorld for EnergyLinks {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Cylinder::new(0.03, 1.));
+        let material = world
+            .resource_mut::<Assets<
+                ExtendedMaterial<
+                    StandardMaterial,
+                    EnergyExt,
+                >,
+            >>()
+            .add(ExtendedMaterial {
+                base: StandardMaterial {
+                    // the energy shader brightens this
+                    // a lot
+                    base_color: (LinearRgba::from(
+                        CYAN_400,
+                    ) * 0.01)
+                        .into(),
+                    unlit: true,
+                    ..default()
+                },
+                extension: EnergyExt::default(),
+            });
+        Self {
+            mesh,
+            material,
+            segments: Vec::new(),
+        }
+    }
+}
+
+/// A beam in the [`EnergyLinks`] pool
+#[derive(Component)]
+struct EnergyLink;
+
+fn project_energy_auras(
+    pylons: Query<
+        (Entity, &EnergyAura, &GlobalTransform),
+        Without<Dying>,
+    >,
+    mut enemies: Query<
+        (
+            Entity,
+            &Team,
+            &GlobalTransform,
+            &mut Health,
+            Option<&mut Shield>,
+            &StatusEffects,
+        ),
+        (Without<EnergyAura>, Without<Dying>),
+    >,
+    mut links: ResMut<EnergyLinks>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    links.segments.clear();
+    for (pylon, aura, pylon_transform) in &pylons {
+        let origin = pylon_transform.translation();
+        for (
+            entity,
+            team,
+            transform,
+            mut health,
+            shield,
+            statuses,
+        ) in &mut enemies
+        {
+            if entity == pylon
+                || *team != Team::Enemy
+                || transform
+                    .translation()
+                    .xz()
+                    .distance(origin.xz())
+                    > aura.radius
+            {
+                continue;
+            }
+
+            match aura.effect {
+                AuraEffect::Heal(rate) => {
+                    health.current = (health.current
+                        + rate * dt)
+                        .min(health.total);
+                    health.last =
+                        health.last.max(health.current);
+                }
+                AuraEffect::Shield { rate, max } => {
+                    if let Some(mut shield) = shield {
+                        shield.max = shield.max.max(max);
+                        shield.current = (shield.current
+                            + rate * dt)
+                            .min(shield.max);
+                    } else {
+                        commands.entity(entity).insert(
+                            Shield {
+                                current: rate * dt,
+                                max,
+                            },
+                        );
+                    }
+                }
+                AuraEffect::Status(effect) => {
+                    let running = statuses
+                        .remaining(effect.kind)
+                        .is_some_and(|remaining| {
+                            remaining > AURA_STATUS_REFRESH
+                        });
+                    if !running {
+                        commands.trigger(StatusApplied {
+                            entity,
+                            source: pylon,
+                            effect,
+                        });
+                    }
+                }
+            }
+
+            links.segments.push((
+                origin + Vec3::Y,
+                transform.translation(),
+            ));
+        }
+    }
+}
+
+fn draw_energy_links(
+    links: Res<EnergyLinks>,
+    mut beams: Query<
+        (&mut Transform, &mut Visibility),
+        With<EnergyLink>,
+    >,
+    mut commands: Commands,
+) {
+    let mut segments = links.segments.iter();
+    for (mut transform, mut visibility) in &mut beams {
+        let Some(&(from, to)) = segments.next() else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        *transform = link_transform(from, to);
+        *visibility = Visibility::Visible;
+    }
+    // grow the pool for whatever didn't fit
+    for &(from, to) in segments {
+        commands.spawn((
+            Name::new("EnergyLink"),
+            EnergyLink,
+            // reused across levels
+            Persistent,
+            Mesh3d(links.mesh.clone()),
+            MeshMaterial3d(links.material.clone()),
+            link_transform(from, to),
+            Visibility::Visible,
+            NotShadowCaster,
+            NotShadowReceiver,
+        ));
+    }
+}
+
+/// Stretch the unit link cylinder from `from` to
+/// `to`
+fn link_transform(from: Vec3, to: Vec3) -> Transform {
+    let span = to - from;
+    Transform::from_translation(from + span / 2.)
+        .with_rotation(Quat::from_rotation_arc(
+            Vec3::Y,
+            span.normalize_or(Vec3::Y),
+        ))
+        .with_scale(Vec3::new(1., span.length(), 1.))
 }
 
 fn rotate_material(
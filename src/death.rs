@@ -0,0 +1,342 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use bevy::{color::palettes::tailwind::*, prelude::*};
+use bevy_rand::{global::GlobalRng, prelude::WyRand};
+use rand::Rng;
+
+use crate::{
+    Despawnable,
+    awareness::TrackEntities,
+    boss::{ArenaLock, Boss},
+    crystals::EnergyAura,
+    enemy::{
+        EnemyBehavior, EnemyState, Knockback, MoveRandomly,
+        Steering,
+    },
+    health::{Died, Health, Team},
+    hitbox::Hurtbox,
+    laser::{LaserHit, RunningLaserProcess},
+    navmesh::NavPath,
+    player::PlayerCharacter,
+    separation::BodyRadius,
+    status::StatusEffects,
+};
+
+/// How long the death sequence plays
+const DEATH_DURATION: Duration = Duration::from_millis(600);
+/// How far a dying enemy sinks into the ground
+const SINK_DEPTH: f32 = 0.5;
+/// How close the player has to get to pick
+/// something up
+const PICKUP_RADIUS: f32 = 0.8;
+
+pub struct DeathPlugin;
+
+/// What happens between an enemy running out of
+/// health and it leaving the world.
+///
+/// A dead enemy gets [`Dying`], which strips its
+/// AI and collision so it stops fighting and
+/// can't be hit again, then shrinks and sinks it
+/// into the ground before despawning it. Its
+/// [`LootTable`] is rolled when it dies, and the
+/// player collects a [`Pickup`] by walking over
+/// it.
+impl Plugin for DeathPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LootTable>()
+            .init_resource::<Coins>()
+            .init_resource::<PickupAssets>()
+            .add_systems(
+                FixedUpdate,
+                (play_death_sequence, collect_pickups),
+            )
+            .add_systems(Update, bob_pickups)
+            .add_observer(on_add_dying)
+            .add_observer(drop_loot);
+    }
+}
+
+/// An entity playing its death sequence
+#[derive(Component)]
+pub struct Dying(pub Timer);
+
+impl Default for Dying {
+    fn default() -> Self {
+        Self(Timer::new(
+            DEATH_DURATION,
+            TimerMode::Once,
+        ))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Loot {
+    Coins(u32),
+    Health(f32),
+}
+
+/// Weighted drops. `nothing` is the weight of
+/// dropping nothing at all.
+#[derive(Resource, Clone, Debug)]
+pub struct LootTable {
+    pub nothing: u32,
+    pub drops: Vec<(Loot, u32)>,
+}
+
+impl Default for LootTable {
+    fn default() -> Self {
+        Self {
+            nothing: 5,
+            drops: vec![
+                (Loot::Coins(1), 4),
+                (Loot::Coins(5), 1),
+                (Loot::Health(10.), 2),
+            ],
+        }
+    }
+}
+
+impl LootTable {
+    fn roll(&self, rng: &mut impl Rng) -> Option<Loot> {
+        let total = self.nothing
+            + self
+                .drops
+                .iter()
+                .map(|(_, weight)| weight)
+                .sum::<u32>();
+        let mut roll = rng.random_range(0..total.max(1));
+        if roll < self.nothing {
+            return None;
+        }
+        roll -= self.nothing;
+        for (loot, weight) in &self.drops {
+            if roll < *weight {
+                return Some(*loot);
+            }
+            roll -= weight;
+        }
+        None
+    }
+}
+
+/// Coins the player has picked up
+#[derive(Resource, Default, Debug)]
+pub struct Coins(pub u32);
+
+/// Loot lying on the ground
+#[derive(Component, Debug)]
+pub struct Pickup(pub Loot);
+
+fn on_add_dying(
+    added: On<Add, Dying>,
+    mut commands: Commands,
+) {
+    commands.entity(added.entity).remove::<(
+        // AI
+        (
+            EnemyBehavior,
+            EnemyState,
+            Steering,
+            MoveRandomly,
+            NavPath,
+            Knockback,
+            TrackEntities,
+            LaserHit,
+            RunningLaserProcess,
+            Boss,
+            ArenaLock,
+        ),
+        // collision and combat
+        (
+            BodyRadius,
+            Hurtbox,
+            StatusEffects,
+            Team,
+            EnergyAura,
+        ),
+    )>();
+}
+
+fn play_death_sequence(
+    mut dying: Query<(Entity, &mut Dying, &mut Transform)>,
+    time: Res<Time>,
+    mut despawnable: ResMut<Despawnable>,
+) {
+    for (entity, mut dying, mut transform) in &mut dying {
+        dying.0.tick(time.delta());
+        let remaining = dying.0.fraction_remaining();
+        transform.scale = Vec3::splat(remaining);
+        transform.translation.y -= SINK_DEPTH
+            * time.delta_secs()
+            / DEATH_DURATION.as_secs_f32();
+        // one full turn a second
+        transform.rotate_y(TAU * time.delta_secs());
+
+        if dying.0.is_finished() {
+            despawnable.0.insert(entity);
+        }
+    }
+}
+
+/// Meshes and materials shared by every
+/// [`Pickup`] of a kind
+#[derive(Resource)]
+struct PickupAssets {
+    coins: (Handle<Mesh>, Handle<StandardMaterial>),
+    health: (Handle<Mesh>, Handle<StandardMaterial>),
+}
+
+impl FromWorld for PickupAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes =
+            world.resource_mut::<Assets<Mesh>>();
+        let coins_mesh =
+            meshes.add(Cylinder::new(0.15, 0.04));
+        let health_mesh = meshes.add(Sphere::new(0.15));
+        let mut materials = world
+            .resource_mut::<Assets<StandardMaterial>>();
+        let mut material = |color: Srgba| {
+            let color = LinearRgba::from(color);
+            materials.add(StandardMaterial {
+                base_color: color.into(),
+                emissive: color * 2.,
+                ..default()
+            })
+        };
+        Self {
+            coins: (coins_mesh, material(YELLOW_400)),
+            health: (health_mesh, material(GREEN_400)),
+        }
+    }
+}
+
+fn drop_loot(
+    died: On<Died>,
+    transforms: Query<&GlobalTransform>,
+    table: Res<LootTable>,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    assets: Res<PickupAssets>,
+    mut commands: Commands,
+) {
+    let Ok(transform) = transforms.get(died.entity) else {
+        return;
+    };
+    let Some(loot) = table.roll(&mut **rng) else {
+        return;
+    };
+
+    let (mesh, material) = match loot {
+        Loot::Coins(_) => assets.coins.clone(),
+        Loot::Health(_) => assets.health.clone(),
+    };
+    commands.spawn((
+        Name::new("Pickup"),
+        Pickup(loot),
+        Mesh3d(mesh),
+        MeshMaterial3d(material),
+        Transform::from_translation(
+            transform.translation().with_y(0.4),
+        ),
+    ));
+}
+
+fn bob_pickups(
+    mut pickups: Query<&mut Transform, With<Pickup>>,
+    time: Res<Time>,
+) {
+    for mut transform in &mut pickups {
+        transform.rotate_y(time.delta_secs());
+        transform.translation.y =
+            0.4 + 0.1 * (time.elapsed_secs() * 3.).sin();
+    }
+}
+
+fn collect_pickups(
+    pickups: Query<(Entity, &Pickup, &Transform)>,
+    mut players: Query<
+        (&Transform, &mut Health),
+        With<PlayerCharacter>,
+    >,
+    mut coins: ResMut<Coins>,
+    mut commands: Commands,
+) {
+    let Ok((player, mut health)) = players.single_mut()
+    else {
+        return;
+    };
+    for (entity, pickup, transform) in &pickups {
+        if transform
+            .translation
+            .xz()
+            .distance(player.translation.xz())
+            > PICKUP_RADIUS
+        {
+            continue;
+        }
+        match pickup.0 {
+            Loot::Coins(amount) => coins.0 += amount,
+            Loot::Health(amount) => {
+                health.current = (health.current + amount)
+                    .min(health.total);
+            }
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn rolls_follow_the_weights() {
+        let table = LootTable::default();
+        let mut rng = WyRand::seed_from_u64(7);
+        let rolls = 12_000;
+        let (mut nothing, mut coins, mut health) =
+            (0i32, 0i32, 0i32);
+        for _ in 0..rolls {
+            match table.roll(&mut rng) {
+                None => nothing += 1,
+                Some(Loot::Coins(_)) => coins += 1,
+                Some(Loot::Health(_)) => health += 1,
+            }
+        }
+        // 5, 5 and 2 out of 12
+        for (count, expected) in
+            [(nothing, 5000), (coins, 5000), (health, 2000)]
+        {
+            assert!(
+                (count - expected).abs() < 300,
+                "{count} rolls, expected about {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn same_seed_rolls_the_same_loot() {
+        let table = LootTable::default();
+        let roll = |seed| {
+            let mut rng = WyRand::seed_from_u64(seed);
+            (0..32)
+                .map(|_| {
+                    format!("{:?}", table.roll(&mut rng))
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(roll(42), roll(42));
+    }
+
+    #[test]
+    fn empty_table_drops_nothing() {
+        let table = LootTable {
+            nothing: 0,
+            drops: Vec::new(),
+        };
+        let mut rng = WyRand::seed_from_u64(1);
+        assert!(table.roll(&mut rng).is_none());
+    }
+}
@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rand::prelude::WyRand;
+use rand::Rng;
+
+use crate::{
+    CurrentLevel,
+    assets::MyStates,
+    health::{Damaged, Died, Health},
+    player::PlayerCharacter,
+};
+
+/// Seconds it takes for remembered damage and
+/// kills to fall off by ~63%
+const MEMORY: f32 = 8.;
+
+pub struct DirectorPlugin;
+
+/// Paces `RandomSpawn` by watching how the player
+/// is doing.
+///
+/// The director tracks player health, recent
+/// damage taken, recent kills, and time since the
+/// last combat, and turns that into an
+/// `intensity` value. A stressed player gets
+/// fewer spawns and easier enemies, a player
+/// cutting through enemies or standing around
+/// gets more.
+impl Plugin for DirectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpawnDirector>()
+            .add_systems(
+                FixedUpdate,
+                update_director
+                    .run_if(in_state(MyStates::Next)),
+            )
+            .add_observer(director_on_damaged)
+            .add_observer(director_on_died);
+    }
+}
+
+/// How much one enemy type shows up, at minimum
+/// and maximum spawn pace
+pub struct EnemyWeight {
+    pub id: &'static str,
+    pub calm: f32,
+    pub hectic: f32,
+}
+
+/// The range the director is allowed to work in
+/// for a level
+pub struct DirectorBounds {
+    /// seconds between spawns at maximum pace
+    pub min_interval: f32,
+    /// seconds between spawns at minimum pace
+    pub max_interval: f32,
+    pub mix: Vec<EnemyWeight>,
+}
+
+impl DirectorBounds {
+    pub fn for_level(level: u32) -> Self {
+        let depth = level.saturating_sub(2) as f32;
+        Self {
+            min_interval: (3. - 0.25 * depth).max(1.5),
+            max_interval: (8. - 0.5 * depth).max(4.),
+            mix: vec![
+                EnemyWeight {
+                    id: "flock-sphere",
+                    calm: 3.,
+                    hectic: 2.,
+                },
+                EnemyWeight {
+                    id: "eye",
+                    calm: 1.,
+                    hectic: 2. + depth * 0.5,
+                },
+                EnemyWeight {
+                    id: "gem-rock",
+                    calm: 0.5,
+                    hectic: 1. + depth * 0.25,
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct SpawnDirector {
+    /// 0 is a relaxed player, 1 is a player under
+    /// heavy pressure
+    intensity: f32,
+    /// 0 is spawning as slowly as the level
+    /// allows, 1 is spawning as fast as the
+    /// level allows
+    pace: f32,
+    recent_damage: f32,
+    recent_kills: f32,
+    time_since_combat: f32,
+    bounds: DirectorBounds,
+    spawn_timer: Timer,
+}
+
+impl Default for SpawnDirector {
+    fn default() -> Self {
+        let bounds = DirectorBounds::for_level(0);
+        Self {
+            intensity: 0.,
+            pace: 0.,
+            recent_damage: 0.,
+            recent_kills: 0.,
+            time_since_combat: 0.,
+            spawn_timer: Timer::from_seconds(
+                bounds.max_interval,
+                TimerMode::Repeating,
+            ),
+            bounds,
+        }
+    }
+}
+
+impl SpawnDirector {
+    /// How much pressure the player is under,
+    /// from 0 to 1. Music and VFX can react
+    /// to this.
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+    /// How fast enemies are being spawned, from 0
+    /// to 1, relative to the current level's
+    /// bounds
+    pub fn pace(&self) -> f32 {
+        self.pace
+    }
+    /// Advance the spawn timer, returning true
+    /// when it's time to spawn an enemy
+    pub fn tick(&mut self, delta: Duration) -> bool {
+        self.spawn_timer.tick(delta).just_finished()
+    }
+    /// Pick an enemy from the level's mix,
+    /// leaning towards the `hectic` weights
+    /// as pace rises. `None` if the mix is empty.
+    pub fn choose_enemy(
+        &self,
+        rng: &mut WyRand,
+    ) -> Option<&'static str> {
+        let weight = |enemy: &EnemyWeight| {
+            enemy.calm.lerp(enemy.hectic, self.pace)
+        };
+        let total: f32 =
+            self.bounds.mix.iter().map(weight).sum();
+        if total <= 0. {
+            return None;
+        }
+        let mut roll = rng.random_range(0.0..total);
+        for enemy in &self.bounds.mix {
+            roll -= weight(enemy);
+            if roll <= 0. {
+                return Some(enemy.id);
+            }
+        }
+        self.bounds.mix.last().map(|enemy| enemy.id)
+    }
+}
+
+fn update_director(
+    mut director: ResMut<SpawnDirector>,
+    current_level: Res<CurrentLevel>,
+    player: Query<&Health, With<PlayerCharacter>>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+
+    if current_level.is_changed() {
+        director.bounds =
+            DirectorBounds::for_level(current_level.0);
+    }
+
+    let decay = (-dt / MEMORY).exp();
+    director.recent_damage *= decay;
+    director.recent_kills *= decay;
+    director.time_since_combat += dt;
+
+    let missing_health = player
+        .single()
+        .map(|health| {
+            1. - (health.current / health.total)
+                .clamp(0., 1.)
+        })
+        .unwrap_or(0.);
+
+    let target_intensity =
+        ((director.recent_damage / 25.).min(1.) * 0.6
+            + missing_health * 0.4)
+            .clamp(0., 1.);
+    director.intensity.smooth_nudge(
+        &target_intensity,
+        2.,
+        dt,
+    );
+
+    // fast kills and downtime both mean the player
+    // wants more to do
+    let appetite = ((director.recent_kills / 4.).min(1.)
+        + (director.time_since_combat / 10.).min(1.))
+        * 0.5;
+    director.pace = (appetite * (1. - director.intensity))
+        .clamp(0., 1.);
+
+    let interval = director.bounds.max_interval.lerp(
+        director.bounds.min_interval,
+        director.pace,
+    );
+    director
+        .spawn_timer
+        .set_duration(Duration::from_secs_f32(interval));
+}
+
+/// Only damage that actually landed counts, and
+/// only fights the player is part of
+fn director_on_damaged(
+    damaged: On<Damaged>,
+    mut director: ResMut<SpawnDirector>,
+    players: Query<(), With<PlayerCharacter>>,
+) {
+    let took_damage = players.contains(damaged.entity);
+    if !took_damage && !players.contains(damaged.attacker) {
+        return;
+    }
+    director.time_since_combat = 0.;
+    if took_damage {
+        director.recent_damage += damaged.amount;
+    }
+}
+
+fn director_on_died(
+    _died: On<Died>,
+    mut director: ResMut<SpawnDirector>,
+) {
+    director.recent_kills += 1.;
+}
@@ -0,0 +1,819 @@
+use std::{
+    collections::VecDeque, f32::consts::TAU, time::Duration,
+};
+
+use bevy::{
+    color::palettes::tailwind::RED_400,
+    math::sampling::UniformMeshSampler, prelude::*,
+};
+use bevy_rand::{global::GlobalRng, prelude::WyRand};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    awareness::{Detectable, DetectedEntities},
+    health::Team,
+    laser::LaserHit,
+    navmesh::{CurrentNavMesh, NavPath, ProcessedNavMesh},
+    projectile::{FireProjectile, ProjectileConfig},
+    separation::BodyRadius,
+    spawn_circle::spawn_systems::{ScaleIn, TranslateUpIn},
+    status::StatusEffects,
+    telegraph::{
+        GroundTelegraph, TelegraphOf, TelegraphShape,
+        Telegraphs,
+    },
+};
+
+/// How far a goal can move before the path to it
+/// is planned again
+const REPATH_DISTANCE: f32 = 0.5;
+/// How close a waypoint has to be to count as
+/// reached
+const WAYPOINT_RADIUS: f32 = 0.2;
+/// Distance from the goal where enemies start
+/// slowing down
+const ARRIVAL_RADIUS: f32 = 1.;
+/// How quickly [`Knockback`] bleeds off
+const KNOCKBACK_DECAY: f32 = 8.;
+/// [`Knockback`] slower than this is finished
+const KNOCKBACK_REST: f32 = 0.1;
+
+pub struct EnemyPlugin;
+
+/// Shared enemy behavior.
+///
+/// Every enemy with an [`EnemyBehavior`] runs the
+/// same state machine, with the [`EnemyState`]
+/// exposed as a component:
+///
+/// ```text
+/// Idle -> Wander -> (Chase) -> Telegraph -> Attack -> Cooldown -> Idle
+///                 any state -> Stunned -> Idle
+/// ```
+///
+/// Which state follows which is configured per
+/// archetype with [`EnemyTransitions`]. Targets
+/// come from the enemy's `DetectedEntities`, so
+/// an enemy that should notice the player needs
+/// `TrackEntities`.
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<EnemyState>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    react_to_detection,
+                    pick_wander_targets,
+                    plan_paths,
+                    move_enemies,
+                    tick_enemy_states,
+                    start_state_timers,
+                    start_attacks,
+                    spin_laser,
+                    apply_knockback,
+                )
+                    .chain()
+                    .in_set(EnemyUpdate),
+            )
+            .add_observer(on_stun);
+    }
+}
+
+/// Where enemies think and move. Anything that
+/// needs to see where enemies ended up this tick
+/// should run after this.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EnemyUpdate;
+
+/// What an enemy is currently doing
+#[derive(
+    Component,
+    Reflect,
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Deserialize,
+)]
+#[reflect(Component)]
+pub enum EnemyState {
+    /// waiting for somewhere to go
+    #[default]
+    Idle,
+    /// walking to a random point on the navmesh
+    Wander,
+    /// walking towards the target
+    Chase,
+    /// about to attack
+    Telegraph,
+    Attack,
+    /// recovering after an attack
+    Cooldown,
+    /// interrupted, see [`Stun`]
+    Stunned,
+}
+
+/// Which state to move to when a state finishes.
+/// Transitions left out of an archetype file keep
+/// their defaults.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct EnemyTransitions {
+    /// something was detected while idle or
+    /// wandering. `None` ignores it.
+    pub detected: Option<EnemyState>,
+    /// the target of a chase was lost
+    pub lost: EnemyState,
+    /// a wander or chase ended with the target
+    /// within `attack_range`
+    pub arrived_in_range: EnemyState,
+    /// a wander ended with the target out of
+    /// `attack_range`
+    pub arrived: EnemyState,
+    pub after_telegraph: EnemyState,
+    pub after_attack: EnemyState,
+    pub after_cooldown: EnemyState,
+    pub after_stun: EnemyState,
+}
+
+impl Default for EnemyTransitions {
+    fn default() -> Self {
+        Self {
+            detected: Some(EnemyState::Chase),
+            lost: EnemyState::Idle,
+            arrived_in_range: EnemyState::Telegraph,
+            arrived: EnemyState::Idle,
+            after_telegraph: EnemyState::Attack,
+            after_attack: EnemyState::Cooldown,
+            after_cooldown: EnemyState::Idle,
+            after_stun: EnemyState::Idle,
+        }
+    }
+}
+
+/// Per-archetype settings for the enemy state
+/// machine
+#[derive(Component, Clone)]
+#[require(
+    EnemyState,
+    Steering,
+    BodyRadius,
+    Team = Team::Enemy
+)]
+pub struct EnemyBehavior {
+    /// units per second while wandering or
+    /// chasing
+    pub speed: f32,
+    /// how close the target has to be for an
+    /// arriving enemy to count as in range
+    pub attack_range: f32,
+    pub transitions: EnemyTransitions,
+    pub telegraph: Duration,
+    pub cooldown: Duration,
+    pub laser: SpinLaser,
+    /// fired at the nearest target when an attack
+    /// starts
+    pub projectile: Option<ProjectileConfig>,
+}
+
+impl EnemyBehavior {
+    /// How long a timed state lasts. Stuns carry
+    /// their own duration.
+    fn duration(
+        &self,
+        state: EnemyState,
+    ) -> Option<Duration> {
+        match state {
+            EnemyState::Telegraph => Some(self.telegraph),
+            EnemyState::Attack => Some(self.laser.duration),
+            EnemyState::Cooldown => Some(self.cooldown),
+            EnemyState::Idle
+            | EnemyState::Wander
+            | EnemyState::Chase
+            | EnemyState::Stunned => None,
+        }
+    }
+}
+
+/// A laser that sweeps around while the enemy is
+/// in [`EnemyState::Attack`]
+#[derive(Clone)]
+pub struct SpinLaser {
+    pub duration: Duration,
+    pub hit: LaserHit,
+    pub spin: Spin,
+    /// turn towards the target when the attack
+    /// starts
+    pub face_target: bool,
+}
+
+impl SpinLaser {
+    /// The ground the laser can sweep over, as
+    /// seen from the enemy
+    pub fn telegraph_shape(&self) -> TelegraphShape {
+        match self.spin {
+            // rolling keeps the beam pointing
+            // forward
+            Spin::LocalZ => TelegraphShape::Line {
+                length: self.hit.length,
+                width: self.hit.width.max(0.5),
+            },
+            Spin::Yaw => TelegraphShape::Circle {
+                radius: self.hit.length,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Spin {
+    /// roll around the local z axis
+    LocalZ,
+    /// turn around the world y axis
+    Yaw,
+}
+
+/// Time left in a timed [`EnemyState`]
+#[derive(Component)]
+pub struct StateTimer(pub Timer);
+
+/// An enemy that moves itself while idle or
+/// wandering, instead of picking random points to
+/// walk to. It's still responsible for leaving
+/// [`EnemyState::Wander`].
+#[derive(Component, Default)]
+pub struct CustomLocomotion;
+
+/// Where a wandering or chasing enemy is headed
+#[derive(Component)]
+pub struct MoveRandomly {
+    pub from: Vec2,
+    pub to: Vec2,
+}
+
+/// Current velocity on the ground plane. Turns
+/// towards the next waypoint instead of snapping.
+#[derive(Component, Default)]
+pub struct Steering {
+    pub velocity: Vec2,
+}
+
+/// Shoves an entity across the ground plane.
+/// Decays every tick and removes itself once it's
+/// spent.
+#[derive(Component, Debug)]
+pub struct Knockback {
+    pub velocity: Vec2,
+}
+
+/// Interrupt whatever an enemy is doing
+#[derive(EntityEvent)]
+pub struct Stun {
+    pub entity: Entity,
+    pub duration: Duration,
+}
+
+fn on_stun(
+    stun: On<Stun>,
+    mut query: Query<&mut EnemyState>,
+    mut commands: Commands,
+) {
+    let Ok(mut state) = query.get_mut(stun.entity) else {
+        return;
+    };
+    *state = EnemyState::Stunned;
+    commands
+        .entity(stun.entity)
+        .remove::<(MoveRandomly, NavPath)>()
+        .insert(StateTimer(Timer::new(
+            stun.duration,
+            TimerMode::Once,
+        )));
+}
+
+/// The closest detected entity, on the ground
+/// plane
+fn nearest_target(
+    detected: &DetectedEntities,
+    position: Vec2,
+    targets: &Query<&GlobalTransform, With<Detectable>>,
+) -> Option<Vec2> {
+    detected
+        .0
+        .iter()
+        .filter_map(|target| targets.get(*target).ok())
+        .map(|target| target.translation().xz())
+        .min_by(|a, b| {
+            a.distance_squared(position)
+                .total_cmp(&b.distance_squared(position))
+        })
+}
+
+fn react_to_detection(
+    mut query: Query<
+        (
+            Entity,
+            &EnemyBehavior,
+            &mut EnemyState,
+            &GlobalTransform,
+            &DetectedEntities,
+        ),
+        (Without<ScaleIn>, Without<TranslateUpIn>),
+    >,
+    targets: Query<&GlobalTransform, With<Detectable>>,
+    mut commands: Commands,
+) {
+    for (entity, behavior, mut state, global, detected) in
+        &mut query
+    {
+        let position = global.translation().xz();
+        let target =
+            nearest_target(detected, position, &targets);
+        match (*state, target) {
+            (
+                EnemyState::Idle | EnemyState::Wander,
+                Some(target),
+            ) => {
+                let Some(next) =
+                    behavior.transitions.detected
+                else {
+                    continue;
+                };
+                if next == *state {
+                    continue;
+                }
+                let mut enemy = commands.entity(entity);
+                enemy.remove::<NavPath>();
+                if matches!(
+                    next,
+                    EnemyState::Chase | EnemyState::Wander
+                ) {
+                    enemy.insert(MoveRandomly {
+                        from: position,
+                        to: target,
+                    });
+                } else {
+                    enemy.remove::<MoveRandomly>();
+                }
+                *state = next;
+            }
+            (EnemyState::Chase, None) => {
+                commands
+                    .entity(entity)
+                    .remove::<(MoveRandomly, NavPath)>();
+                *state = behavior.transitions.lost;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn pick_wander_targets(
+    mut query: Query<
+        (Entity, &Transform, &mut EnemyState),
+        (
+            With<EnemyBehavior>,
+            Without<CustomLocomotion>,
+            Without<ScaleIn>,
+            Without<TranslateUpIn>,
+        ),
+    >,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    mut commands: Commands,
+    current_navmesh: Query<(&ProcessedNavMesh, &Mesh3d)>,
+    meshes: Res<Assets<Mesh>>,
+    navmeshes: Res<Assets<vleue_navigator::NavMesh>>,
+) {
+    if !query
+        .iter()
+        .any(|(_, _, state)| *state == EnemyState::Idle)
+    {
+        return;
+    }
+
+    let Ok((navmesh, mesh)) = current_navmesh.single()
+    else {
+        return;
+    };
+
+    let navmesh = navmeshes.get(&navmesh.0).expect("a valid ProcessedNavMesh should fetch a valid NavMesh");
+    let mesh = meshes
+        .get(&mesh.0)
+        .expect("a valid Mesh3d should fetch a valid Mesh");
+
+    let sampler = UniformMeshSampler::try_new(
+        mesh.triangles().unwrap(),
+    )
+    .unwrap();
+
+    for (entity, transform, mut state) in &mut query {
+        if *state != EnemyState::Idle {
+            continue;
+        }
+        let sample = rng.sample(&sampler);
+        // we're using the mesh to sample so it *should*
+        // always find a valid location, and if it doesn't
+        // we try again next frame
+        if navmesh.transformed_is_in_mesh(sample.with_y(0.))
+        {
+            commands.entity(entity).insert(MoveRandomly {
+                from: transform.translation.xz(),
+                to: sample.xz(),
+            });
+            *state = EnemyState::Wander;
+        }
+    }
+}
+
+fn plan_paths(
+    query: Query<(
+        Entity,
+        &GlobalTransform,
+        &MoveRandomly,
+        Option<&NavPath>,
+    )>,
+    navmesh: CurrentNavMesh,
+    mut commands: Commands,
+) {
+    for (entity, global, move_randomly, path) in &query {
+        if path.is_some_and(|path| {
+            path.goal.distance(move_randomly.to)
+                < REPATH_DISTANCE
+        }) {
+            continue;
+        }
+        let waypoints = navmesh
+            .path(
+                global.translation().xz(),
+                move_randomly.to,
+            )
+            // no path, for example when the goal is off
+            // the navmesh, so head straight for it
+            .unwrap_or_else(|| {
+                VecDeque::from([move_randomly.to])
+            });
+        commands.entity(entity).insert(NavPath {
+            goal: move_randomly.to,
+            waypoints,
+        });
+    }
+}
+
+fn move_enemies(
+    mut query: Query<(
+        Entity,
+        &EnemyBehavior,
+        &mut EnemyState,
+        &mut Steering,
+        &mut Transform,
+        &GlobalTransform,
+        &mut MoveRandomly,
+        Option<&mut NavPath>,
+        Option<&DetectedEntities>,
+        Option<&StatusEffects>,
+    )>,
+    targets: Query<&GlobalTransform, With<Detectable>>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for (
+        entity,
+        behavior,
+        mut state,
+        mut steering,
+        mut transform,
+        global,
+        mut move_randomly,
+        path,
+        detected,
+        statuses,
+    ) in &mut query
+    {
+        let max_speed = behavior.speed
+            * statuses.map_or(
+                1.,
+                StatusEffects::speed_multiplier,
+            );
+        let position = global.translation().xz();
+        let target = detected.and_then(|detected| {
+            nearest_target(detected, position, &targets)
+        });
+        let in_range = target.is_some_and(|target| {
+            target.distance(position)
+                < behavior.attack_range
+        });
+
+        let arrived = match *state {
+            EnemyState::Chase => {
+                if let Some(target) = target {
+                    move_randomly.to = target;
+                }
+                in_range
+            }
+            EnemyState::Wander => {
+                position.distance(move_randomly.to) < 0.1
+            }
+            _ => continue,
+        };
+        if arrived {
+            commands
+                .entity(entity)
+                .remove::<(MoveRandomly, NavPath)>();
+            steering.velocity = Vec2::ZERO;
+            *state = if in_range {
+                behavior.transitions.arrived_in_range
+            } else {
+                behavior.transitions.arrived
+            };
+            continue;
+        }
+
+        // the last waypoint is wherever the goal was
+        // when the path was planned, so aim for where
+        // it is now instead
+        let waypoint = match path {
+            Some(mut path) => {
+                while path.waypoints.len() > 1
+                    && path.waypoints[0].distance(position)
+                        < WAYPOINT_RADIUS
+                {
+                    path.waypoints.pop_front();
+                }
+                if path.waypoints.len() > 1 {
+                    path.waypoints[0]
+                } else {
+                    move_randomly.to
+                }
+            }
+            None => move_randomly.to,
+        };
+
+        let speed = if waypoint == move_randomly.to {
+            let remaining = position.distance(waypoint);
+            max_speed
+                * (remaining / ARRIVAL_RADIUS)
+                    .clamp(0.25, 1.)
+        } else {
+            max_speed
+        };
+        let desired = (waypoint - position)
+            .normalize_or_zero()
+            * speed;
+        steering.velocity.smooth_nudge(
+            &desired,
+            8.,
+            time.delta_secs(),
+        );
+        let movement =
+            steering.velocity * time.delta_secs();
+        transform.translation += movement.extend(0.).xzy();
+
+        let target_rotation = transform.looking_at(
+            waypoint.extend(global.translation().y).xzy(),
+            Vec3::Y,
+        );
+        transform.rotation.smooth_nudge(
+            &target_rotation.rotation,
+            5.,
+            time.delta_secs(),
+        );
+    }
+}
+
+fn tick_enemy_states(
+    mut query: Query<(
+        Entity,
+        &EnemyBehavior,
+        &mut EnemyState,
+        &mut StateTimer,
+    )>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for (entity, behavior, mut state, mut timer) in
+        &mut query
+    {
+        if !timer.0.tick(time.delta()).is_finished() {
+            continue;
+        }
+        let transitions = &behavior.transitions;
+        let next = match *state {
+            EnemyState::Telegraph => {
+                transitions.after_telegraph
+            }
+            EnemyState::Attack => transitions.after_attack,
+            EnemyState::Cooldown => {
+                transitions.after_cooldown
+            }
+            EnemyState::Stunned => transitions.after_stun,
+            EnemyState::Idle
+            | EnemyState::Wander
+            | EnemyState::Chase => continue,
+        };
+        commands.entity(entity).remove::<StateTimer>();
+        *state = next;
+    }
+}
+
+fn start_state_timers(
+    query: Query<
+        (Entity, &EnemyBehavior, &EnemyState),
+        Changed<EnemyState>,
+    >,
+    mut commands: Commands,
+) {
+    for (entity, behavior, state) in &query {
+        if let Some(duration) = behavior.duration(*state) {
+            commands.entity(entity).insert(StateTimer(
+                Timer::new(duration, TimerMode::Once),
+            ));
+        }
+    }
+}
+
+/// Turn the laser on and off with
+/// [`EnemyState::Attack`]
+fn start_attacks(
+    mut query: Query<
+        (
+            Entity,
+            &EnemyBehavior,
+            &EnemyState,
+            &mut Transform,
+            &GlobalTransform,
+            &DetectedEntities,
+        ),
+        Changed<EnemyState>,
+    >,
+    targets: Query<&GlobalTransform, With<Detectable>>,
+    mut commands: Commands,
+) {
+    for (
+        entity,
+        behavior,
+        state,
+        mut transform,
+        global,
+        detected,
+    ) in &mut query
+    {
+        if *state != EnemyState::Telegraph {
+            commands
+                .entity(entity)
+                .despawn_related::<Telegraphs>();
+        }
+        if *state != EnemyState::Attack {
+            commands.entity(entity).remove::<LaserHit>();
+        }
+        let winding_up = match *state {
+            EnemyState::Telegraph => {
+                !behavior.telegraph.is_zero()
+            }
+            // attacks without a telegraph aim when
+            // they start
+            EnemyState::Attack => {
+                behavior.telegraph.is_zero()
+            }
+            EnemyState::Idle
+            | EnemyState::Wander
+            | EnemyState::Chase
+            | EnemyState::Cooldown
+            | EnemyState::Stunned => false,
+        };
+
+        let position = global.translation();
+        let target = detected
+            .0
+            .iter()
+            .filter_map(|target| {
+                targets.get(*target).ok().map(|transform| {
+                    (*target, transform.translation())
+                })
+            })
+            .min_by(|(_, a), (_, b)| {
+                a.distance_squared(position).total_cmp(
+                    &b.distance_squared(position),
+                )
+            });
+        if winding_up && behavior.laser.face_target {
+            if let Some((_, target_position)) = target {
+                transform.look_at(target_position, Vec3::Y);
+            }
+        }
+
+        match *state {
+            EnemyState::Telegraph if winding_up => {
+                let yaw = transform
+                    .forward()
+                    .xz()
+                    .try_normalize()
+                    .map(|forward| {
+                        Quat::from_rotation_arc(
+                            Vec3::NEG_Z,
+                            forward.extend(0.).xzy(),
+                        )
+                    })
+                    .unwrap_or_default();
+                commands.spawn((
+                    Name::new("Telegraph"),
+                    GroundTelegraph::new(
+                        behavior.laser.telegraph_shape(),
+                        behavior.telegraph,
+                        RED_400.with_alpha(0.6),
+                    ),
+                    Transform::from_translation(
+                        position.with_y(0.02),
+                    )
+                    .with_rotation(yaw),
+                    TelegraphOf(entity),
+                ));
+            }
+            EnemyState::Attack => {
+                commands
+                    .entity(entity)
+                    .insert(behavior.laser.hit.clone());
+                let (Some(config), Some((target, _))) =
+                    (&behavior.projectile, target)
+                else {
+                    continue;
+                };
+                commands.queue(FireProjectile {
+                    attacker: entity,
+                    origin: position,
+                    target,
+                    config: config.clone(),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+fn spin_laser(
+    mut query: Query<(
+        &EnemyBehavior,
+        &EnemyState,
+        &mut Transform,
+        &GlobalTransform,
+        &LaserHit,
+    )>,
+    time: Res<Time>,
+    mut gizmos: Gizmos,
+) {
+    for (behavior, state, mut transform, global, laser) in
+        &mut query
+    {
+        if *state != EnemyState::Attack {
+            continue;
+        }
+
+        if let Some(end) = laser.end(global) {
+            gizmos.line(global.translation(), end, RED_400);
+        }
+
+        match behavior.laser.spin {
+            Spin::LocalZ => {
+                transform.rotate_local_z(
+                    TAU * time.delta_secs(),
+                );
+            }
+            Spin::Yaw => {
+                transform.rotate(Quat::from_rotation_y(
+                    TAU * time.delta_secs(),
+                ));
+            }
+        }
+    }
+}
+
+fn apply_knockback(
+    mut query: Query<(
+        Entity,
+        &mut Knockback,
+        &mut Transform,
+    )>,
+    navmesh: CurrentNavMesh,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    let dt = time.delta_secs();
+    for (entity, mut knockback, mut transform) in &mut query
+    {
+        // knocked along walls rather than off the
+        // level
+        if let Some(step) = navmesh.slide(
+            transform.translation.xz(),
+            knockback.velocity * dt,
+        ) {
+            transform.translation += step.extend(0.).xzy();
+        }
+
+        knockback.velocity *= (-KNOCKBACK_DECAY * dt).exp();
+        if knockback.velocity.length() < KNOCKBACK_REST {
+            commands.entity(entity).remove::<Knockback>();
+        }
+    }
+}
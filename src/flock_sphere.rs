@@ -1,309 +1,288 @@
-use std::{
-    f32::consts::{FRAC_PI_4, TAU},
-    time::Duration,
-};
+use std::time::Duration;
 
-use bevy::{
-    color::palettes::tailwind::RED_400,
-    math::{
-        bounding::{BoundingCircle, RayCast2d},
-        sampling::UniformMeshSampler,
-    },
-    prelude::*,
-};
-use bevy_rand::{global::GlobalRng, prelude::WyRand};
-use rand::Rng;
+use bevy::{platform::collections::HashMap, prelude::*};
 
 use crate::{
-    MoveRandomly,
-    assets::GltfAssets,
-    health::{Attack, Health},
-    navmesh::ProcessedNavMesh,
-    player::PlayerCharacter,
-    spawn_circle::spawn_systems::{
-        AppSpawnExt, ScaleIn, TranslateUpIn,
+    enemy::{
+        EnemyBehavior, EnemyState, EnemyUpdate, Steering,
     },
+    navmesh::CurrentNavMesh,
+    player::PlayerCharacter,
+    spawn_circle::spawn_systems::{ScaleIn, TranslateUpIn},
+    status::StatusEffects,
 };
 
 pub struct FlockSpherePlugin;
 
+/// Flock spheres move as boids: they keep their
+/// distance from each other, match their
+/// neighbors' heading, stay together, and drift
+/// towards the player. After flocking for a while
+/// each sphere stops to attack.
 impl Plugin for FlockSpherePlugin {
     fn build(&self, app: &mut App) {
-        app.register_spawn_system(
-            "flock-sphere".to_string(),
-            one_shot_spawn_flock_sphere,
-        )
-        .add_systems(
+        app.init_resource::<FlockSettings>().add_systems(
             FixedUpdate,
-            (
-                trigger_move_flock_sphere_temp.run_if(
-                    any_match_filter::<(
-                        With<FlockSphere>,
-                        Without<MoveRandomly>,
-                        Without<ScaleIn>,
-                        Without<TranslateUpIn>,
-                    )>,
-                ),
-                move_flock_spheres_temp,
-                spin_laser,
-            ),
+            flock.in_set(EnemyUpdate),
         );
     }
 }
 
-#[derive(Component, Reflect)]
-#[reflect(Component)]
-#[type_path = "api"]
-pub struct FlockSphere;
-
-#[derive(Component)]
-#[require(SpinLaserTimer(Timer::from_seconds(
-    5.,
-    TimerMode::Once
-)))]
-struct SpinLaser;
-
-#[derive(Component)]
-struct SpinLaserTimer(Timer);
-
-fn move_flock_spheres_temp(
-    mut query: Query<
-        (
-            Entity,
-            &mut Transform,
-            &GlobalTransform,
-            &MoveRandomly,
-        ),
-        With<FlockSphere>,
-    >,
-    mut commands: Commands,
-    time: Res<Time>,
-    // mut gizmos: Gizmos,
-) {
-    for (entity, mut transform, global, move_randomly) in
-        &mut query
-    {
-        // gizmos.arrow(
-        //     global.translation(),
-        //     move_randomly
-        //         .to
-        //         .extend(global.translation().y)
-        //         .xzy(),
-        //     Color::WHITE,
-        // );
-        // gizmos.sphere(
-        //     move_randomly.to.extend(0.).xzy(),
-        //     0.5,
-        //     GREEN_400,
-        // );
-        if global
-            .translation()
-            .xz()
-            .distance(move_randomly.to)
-            < 0.1
-        {
-            commands
-                .entity(entity)
-                .remove::<MoveRandomly>()
-                .insert(SpinLaser);
-        } else {
-            let direction = (move_randomly.to
-                - global.translation().xz())
-            .normalize();
-            let movement = direction * time.delta_secs();
-            transform.translation +=
-                movement.extend(0.).xzy();
+/// Weights for the flocking rules
+#[derive(Resource)]
+pub struct FlockSettings {
+    /// how far a sphere looks for flockmates.
+    /// Also the cell size of the spatial
+    /// grid.
+    pub neighbor_radius: f32,
+    /// flockmates closer than this push apart
+    pub separation_radius: f32,
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+    /// pull towards the player
+    pub goal: f32,
+    /// how long a sphere flocks before it attacks
+    pub flock_time: Duration,
+}
 
-            let target_rotation = transform.looking_at(
-                move_randomly
-                    .to
-                    .extend(global.translation().y)
-                    .xzy(),
-                Vec3::Y,
-            );
-            transform.rotation.smooth_nudge(
-                &target_rotation.rotation,
-                5.,
-                time.delta_secs(),
-            );
+impl Default for FlockSettings {
+    fn default() -> Self {
+        Self {
+            neighbor_radius: 2.5,
+            separation_radius: 1.,
+            separation: 2.,
+            alignment: 1.,
+            cohesion: 0.5,
+            goal: 0.5,
+            flock_time: Duration::from_secs(4),
         }
     }
 }
-fn trigger_move_flock_sphere_temp(
-    query: Query<
-        (Entity, &Transform),
-        (
-            With<FlockSphere>,
-            Without<MoveRandomly>,
-            Without<ScaleIn>,
-            Without<TranslateUpIn>,
-            Without<SpinLaser>,
-        ),
-    >,
-    mut rng: Single<&mut WyRand, With<GlobalRng>>,
-    mut commands: Commands,
-    current_navmesh: Query<(&ProcessedNavMesh, &Mesh3d)>,
-    meshes: Res<Assets<Mesh>>,
-    navmeshes: Res<Assets<vleue_navigator::NavMesh>>,
-) {
-    let Ok((navmesh, mesh)) = current_navmesh.single()
-    else {
-        return;
-    };
 
-    let navmesh = navmeshes.get(&navmesh.0).expect("a valid ProcessedNavMesh should fetch a valid NavMesh");
-    let mesh = meshes
-        .get(&mesh.0)
-        .expect("a valid Mesh3d should fetch a valid Mesh");
+/// Time left before a flocking sphere attacks
+#[derive(Component)]
+struct FlockTimer(Timer);
 
-    let sampler = UniformMeshSampler::try_new(
-        mesh.triangles().unwrap(),
-    )
-    .unwrap();
+/// Flock spheres bucketed by position, so each
+/// sphere only has to look at nearby cells for
+/// flockmates
+struct FlockGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<usize>>,
+}
 
-    for (entity, transform) in &query {
-        let sample = rng.sample(&sampler);
-        // TODO: loop until finding a valid position in the navmesh.
-        // but for now we're using the mesh to sample so it *should* always
-        // find a valid location
-        if navmesh.transformed_is_in_mesh(sample.with_y(0.))
+impl FlockGrid {
+    fn new(cell_size: f32, positions: &[Vec2]) -> Self {
+        let mut grid = Self {
+            cell_size,
+            cells: HashMap::default(),
+        };
+        for (index, position) in
+            positions.iter().enumerate()
         {
-            commands.entity(entity).insert(MoveRandomly {
-                from: transform.translation.xz(),
-                to: sample.xz(),
-            });
+            grid.cells
+                .entry(grid.cell(*position))
+                .or_default()
+                .push(index);
         }
+        grid
+    }
+    fn cell(&self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).floor().as_ivec2()
+    }
+    /// Everything in the 3x3 cells around
+    /// `position`
+    fn nearby(
+        &self,
+        position: Vec2,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let center = self.cell(position);
+        (-1..=1)
+            .flat_map(move |x| {
+                (-1..=1)
+                    .map(move |y| center + IVec2::new(x, y))
+            })
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
     }
 }
 
-fn one_shot_spawn_flock_sphere(
-    mut transform: In<Transform>,
-    mut commands: Commands,
-    gltf: ResMut<GltfAssets>,
-    gltfs: Res<Assets<Gltf>>,
-) {
-    transform.translation.y = 0.5;
-
-    commands.spawn(
-        (
-            Name::new("FlockSphere"),
-            FlockSphere,
-            Health::new(25.),
-            SceneRoot(
-                gltfs.get(&gltf.misc).unwrap().named_scenes
-                    ["flock-sphere"]
-                    .clone(),
-            ),
-            *transform,
-            ScaleIn(Timer::new(
-                Duration::from_millis(100),
-                TimerMode::Once,
-            )),
-            TranslateUpIn {
-                timer: Timer::new(
-                    Duration::from_millis(250),
-                    TimerMode::Once,
-                ),
-                target: transform.translation,
-            },
-        ),
-    );
-}
-
-#[derive(Component)]
-struct LaserCooldown(Timer);
-
-fn spin_laser(
-    mut query: Query<
+fn flock(
+    mut spheres: Query<
         (
             Entity,
-            &mut SpinLaserTimer,
+            &EnemyBehavior,
+            &mut EnemyState,
+            &mut Steering,
             &mut Transform,
+            &GlobalTransform,
+            Option<&mut FlockTimer>,
+            Option<&StatusEffects>,
+        ),
+        (
+            With<FlockSphere>,
+            Without<ScaleIn>,
+            Without<TranslateUpIn>,
         ),
-        With<FlockSphere>,
     >,
-    mut commands: Commands,
-    time: Res<Time>,
-    mut gizmos: Gizmos,
-    players: Query<
-        (Entity, &GlobalTransform),
+    player: Query<
+        &GlobalTransform,
         (
             With<PlayerCharacter>,
-            Without<SpinLaserTimer>,
+            Without<FlockSphere>,
         ),
     >,
-    mut cooldowns: Query<&mut LaserCooldown>,
+    settings: Res<FlockSettings>,
+    navmesh: CurrentNavMesh,
+    mut commands: Commands,
+    time: Res<Time>,
 ) {
-    for (entity, mut laser_timer, mut transform) in
-        &mut query
-    {
-        gizmos.ray(
-            transform.translation,
-            transform.forward() * 2.,
-            RED_400,
-        );
+    let dt = time.delta_secs();
+    let player = player
+        .single()
+        .ok()
+        .map(|player| player.translation().xz());
 
-        if laser_timer.0.tick(time.delta()).just_finished()
-        {
-            commands.entity(entity).remove::<(
-                SpinLaser,
-                SpinLaserTimer,
-                LaserCooldown,
-            )>();
-        } else {
-            transform.rotate(Quat::from_rotation_y(
-                TAU * time.delta_secs(),
-            ));
+    // spheres that are attacking still count as
+    // flockmates, they just don't move
+    let (positions, velocities): (Vec<Vec2>, Vec<Vec2>) =
+        spheres
+            .iter()
+            .map(|(_, _, _, steering, _, global, _, _)| {
+                (
+                    global.translation().xz(),
+                    steering.velocity,
+                )
+            })
+            .unzip();
+    let grid = FlockGrid::new(
+        settings.neighbor_radius,
+        &positions,
+    );
 
-            if let Ok(mut timer) = cooldowns.get_mut(entity)
-            {
-                if timer
-                    .0
-                    .tick(time.delta())
-                    .just_finished()
+    for (
+        entity,
+        behavior,
+        mut state,
+        mut steering,
+        mut transform,
+        global,
+        timer,
+        statuses,
+    ) in &mut spheres
+    {
+        let position = global.translation().xz();
+        match (*state, timer) {
+            (EnemyState::Idle, _)
+            | (EnemyState::Wander, None) => {
+                commands.entity(entity).insert(FlockTimer(
+                    Timer::new(
+                        settings.flock_time,
+                        TimerMode::Once,
+                    ),
+                ));
+                *state = EnemyState::Wander;
+            }
+            (EnemyState::Wander, Some(mut timer)) => {
+                if timer.0.tick(time.delta()).is_finished()
                 {
                     commands
                         .entity(entity)
-                        .remove::<LaserCooldown>();
-                    // can hit
-                } else {
+                        .remove::<FlockTimer>();
+                    steering.velocity = Vec2::ZERO;
+                    let in_range =
+                        player.is_some_and(|player| {
+                            player.distance(position)
+                                < behavior.attack_range
+                        });
+                    *state = if in_range {
+                        behavior
+                            .transitions
+                            .arrived_in_range
+                    } else {
+                        behavior.transitions.arrived
+                    };
                     continue;
                 }
             }
+            _ => continue,
+        }
 
-            for (player_entity, player) in &players {
-                // Did laser hit player?
-                let player_circle = BoundingCircle {
-                    center: player.translation().xz(),
-                    circle: Circle { radius: 0.5 },
-                };
-
-                let ray_cast = RayCast2d::new(
-                    transform.translation.xz(),
-                    Dir2::new(transform.forward().xz())
-                        .unwrap(),
-                    2.,
-                );
+        let mut separation = Vec2::ZERO;
+        let mut heading = Vec2::ZERO;
+        let mut center = Vec2::ZERO;
+        let mut neighbors = 0;
+        for other in grid.nearby(position) {
+            let offset = position - positions[other];
+            let distance = offset.length();
+            // skips this sphere too
+            if distance <= f32::EPSILON
+                || distance > settings.neighbor_radius
+            {
+                continue;
+            }
+            if distance < settings.separation_radius {
+                separation +=
+                    offset / (distance * distance);
+            }
+            heading += velocities[other];
+            center += positions[other];
+            neighbors += 1;
+        }
 
-                let Some(_) = ray_cast
-                    .circle_intersection_at(&player_circle)
-                else {
-                    continue;
-                };
+        let mut acceleration =
+            separation * settings.separation;
+        if neighbors > 0 {
+            let neighbors = neighbors as f32;
+            acceleration += (heading / neighbors
+                - steering.velocity)
+                * settings.alignment;
+            acceleration += (center / neighbors - position)
+                * settings.cohesion;
+        }
+        if let Some(player) = player {
+            acceleration += (player - position)
+                .normalize_or_zero()
+                * settings.goal;
+        }
+        steering.velocity = (steering.velocity
+            + acceleration * dt)
+            .clamp_length_max(
+                behavior.speed
+                    * statuses.map_or(
+                        1.,
+                        StatusEffects::speed_multiplier,
+                    ),
+            );
 
-                commands.trigger(Attack {
-                    attacker: entity,
-                    receiver: player_entity,
-                    strength: 5.,
-                });
+        // stay on the navmesh, sliding along edges
+        let Some(step) =
+            navmesh.slide(position, steering.velocity * dt)
+        else {
+            steering.velocity = Vec2::ZERO;
+            continue;
+        };
+        let velocity = step / dt;
+        steering.velocity = velocity;
+        transform.translation += step.extend(0.).xzy();
 
-                commands.entity(entity).insert(
-                    LaserCooldown(Timer::from_seconds(
-                        0.2,
-                        TimerMode::Once,
-                    )),
-                );
-            }
+        if velocity.length_squared() > f32::EPSILON {
+            let target_rotation = transform.looking_to(
+                velocity.extend(0.).xzy(),
+                Vec3::Y,
+            );
+            transform.rotation.smooth_nudge(
+                &target_rotation.rotation,
+                5.,
+                dt,
+            );
         }
     }
 }
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[type_path = "api"]
+pub struct FlockSphere;
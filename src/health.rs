@@ -1,4 +1,4 @@
-use std::f32::consts::FRAC_PI_4;
+use std::{collections::BTreeMap, f32::consts::FRAC_PI_4};
 
 use bevy::{
     color::palettes::tailwind::*,
@@ -7,10 +7,14 @@ use bevy::{
     render::render_resource::AsBindGroup,
     shader::ShaderRef,
 };
+use serde::Deserialize;
 
 use crate::{
-    ActivePlayerCamera, Despawnable, ExpectedEnemies,
+    ActivePlayerCamera, ExpectedEnemies,
+    death::Dying,
+    invulnerability::Invulnerability,
     player::PlayerCharacter,
+    status::{StatusApplied, StatusEffect, StatusEffects},
 };
 
 pub struct HealthPlugin;
@@ -39,9 +43,78 @@ pub struct Attack {
     #[event_target]
     pub receiver: Entity,
     pub strength: f32,
+    pub damage_type: DamageType,
+    /// status effects applied to the receiver
+    pub effects: Vec<StatusEffect>,
 }
 
+/// What kind of damage an [`Attack`] deals
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Deserialize,
+)]
+pub enum DamageType {
+    /// hammer hits
+    Blunt,
+    Laser,
+    /// projectiles and auras
+    Energy,
+    Explosive,
+    /// burn status ticks, which go through
+    /// invulnerability without starting it
+    Burn,
+}
+
+/// Damage multipliers by [`DamageType`]. Below 1
+/// resists, above 1 is a weakness, and anything
+/// missing takes normal damage.
+#[derive(Component, Clone, Debug, Default)]
+pub struct Resistances(pub BTreeMap<DamageType, f32>);
+
+impl Resistances {
+    pub fn multiplier(
+        &self,
+        damage_type: DamageType,
+    ) -> f32 {
+        self.0.get(&damage_type).copied().unwrap_or(1.)
+    }
+}
+
+/// Triggered by `on_attack` when an [`Attack`]
+/// gets through, after resistances, shields and
+/// invulnerability. Blocked hits don't trigger
+/// it.
+#[derive(EntityEvent)]
+pub struct Damaged {
+    pub entity: Entity,
+    /// may be dying, like an explosive enemy
+    pub attacker: Entity,
+    /// health actually lost
+    pub amount: f32,
+}
+
+/// Triggered once when an entity runs out of
+/// health
+#[derive(EntityEvent)]
+pub struct Died {
+    pub entity: Entity,
+    /// whoever landed the last [`Attack`], which
+    /// may have despawned since
+    pub killer: Option<Entity>,
+}
+
+/// Whoever most recently attacked this entity
+#[derive(Component, Debug)]
+pub struct LastAttacker(pub Entity);
+
 #[derive(Component)]
+#[require(StatusEffects)]
 pub struct Health {
     /// the max total health an entity can have
     pub total: f32,
@@ -62,6 +135,27 @@ impl Health {
     }
 }
 
+/// Soaks up damage before it reaches [`Health`]
+#[derive(Component, Debug)]
+pub struct Shield {
+    pub current: f32,
+    pub max: f32,
+}
+
+/// Which side an entity fights on. Lasers only
+/// hurt hostile teams.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Team {
+    Player,
+    Enemy,
+}
+
+impl Team {
+    pub fn is_hostile_to(self, other: Team) -> bool {
+        self != other
+    }
+}
+
 #[derive(Component)]
 #[relationship(relationship_target = HealthBarDisplay)]
 pub struct HealthBarOf {
@@ -75,37 +169,95 @@ pub struct HealthBarDisplay(Vec<Entity>);
 
 fn remove_dead(
     query: Query<
-        (Entity, &Health),
-        Without<PlayerCharacter>,
+        (Entity, &Health, Option<&LastAttacker>),
+        (Without<PlayerCharacter>, Without<Dying>),
     >,
-    // mut commands: Commands,
-    mut despawnable: ResMut<Despawnable>,
+    mut commands: Commands,
 ) {
-    for (entity, health) in &query {
+    for (entity, health, last_attacker) in &query {
         if health.current <= 0.1 {
-            // TODO: despawn without "try_despawn"; too late in the
-            // jam to debug
-            despawnable.0.insert(entity);
-
-            // commands.entity(entity).try_despawn();
+            // the death sequence despawns it once it
+            // finishes
+            commands
+                .entity(entity)
+                .insert(Dying::default());
+            commands.trigger(Died {
+                entity,
+                killer: last_attacker
+                    .map(|attacker| attacker.0),
+            });
         }
     }
 }
 
 fn on_attack(
     attack: On<Attack>,
-    mut health_counts: Query<&mut Health>,
+    mut health_counts: Query<(
+        &mut Health,
+        Option<&mut Shield>,
+        Option<&Resistances>,
+        Option<&mut Invulnerability>,
+        &mut StatusEffects,
+    )>,
     mut expected: ResMut<ExpectedEnemies>,
+    mut commands: Commands,
 ) {
     expected.seen_any = true;
-    info!("process attack");
-    let Ok(mut health) =
-        health_counts.get_mut(attack.receiver)
+    let Ok((
+        mut health,
+        shield,
+        resistances,
+        invulnerability,
+        mut statuses,
+    )) = health_counts.get_mut(attack.receiver)
     else {
         return;
     };
+    if let Some(mut invulnerability) = invulnerability
+        .filter(|_| attack.damage_type != DamageType::Burn)
+    {
+        if invulnerability.is_active() {
+            return;
+        }
+        invulnerability.trigger();
+    }
+    commands
+        .entity(attack.receiver)
+        .insert(LastAttacker(attack.attacker));
+
+    // resistances and vulnerability apply before
+    // shields, so a shield soaks up what actually
+    // gets through
+    let mut strength = attack.strength
+        * resistances.map_or(1., |resistances| {
+            resistances.multiplier(attack.damage_type)
+        })
+        * statuses.damage_multiplier();
+    strength = statuses.absorb(strength);
+    if let Some(mut shield) = shield {
+        let absorbed = shield.current.min(strength);
+        shield.current -= absorbed;
+        strength -= absorbed;
+    }
+    // fully resisted or soaked up, so it's blocked
+    // like an invulnerable hit
+    if strength <= 0. {
+        return;
+    }
+    health.current -= strength;
+    commands.trigger(Damaged {
+        entity: attack.receiver,
+        attacker: attack.attacker,
+        amount: strength,
+    });
 
-    health.current -= attack.strength;
+    for effect in &attack.effects {
+        commands.trigger(StatusApplied {
+            entity: attack.receiver,
+            source: attack.attacker,
+            effect: *effect,
+        });
+    }
 }
 
 fn lerp_health(
@@ -220,3 +372,54 @@ fn sync_health(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(
+        world: &mut World,
+        receiver: Entity,
+        damage_type: DamageType,
+    ) -> f32 {
+        world.trigger(Attack {
+            attacker: Entity::PLACEHOLDER,
+            receiver,
+            strength: 10.,
+            damage_type,
+            effects: Vec::new(),
+        });
+        world.flush();
+        world.get::<Health>(receiver).unwrap().current
+    }
+
+    #[test]
+    fn resistances_scale_damage_by_type() {
+        let mut world = World::new();
+        world.init_resource::<ExpectedEnemies>();
+        world.add_observer(on_attack);
+        let receiver = world
+            .spawn((
+                Health::new(100.),
+                Resistances(BTreeMap::from([
+                    (DamageType::Laser, 0.25),
+                    (DamageType::Blunt, 2.),
+                ])),
+            ))
+            .id();
+
+        assert_eq!(
+            hit(&mut world, receiver, DamageType::Laser),
+            97.5
+        );
+        assert_eq!(
+            hit(&mut world, receiver, DamageType::Blunt),
+            77.5
+        );
+        // anything missing takes normal damage
+        assert_eq!(
+            hit(&mut world, receiver, DamageType::Energy),
+            67.5
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use std::time::Duration;
+
+use bevy::{
+    ecs::entity::EntityHashMap,
+    math::bounding::{BoundingCircle, RayCast2d},
+    prelude::*,
+};
+
+use crate::{
+    health::{Attack, DamageType, Team},
+    status::StatusEffect,
+};
+
+pub struct HitboxPlugin;
+
+/// Hit detection between attacks and the bodies
+/// they can hurt.
+///
+/// Anything that can be hurt has a [`Hurtbox`]
+/// sized to its body. Attacks spawn a [`Hitbox`],
+/// either as its own entity that lives for a
+/// short window or on an entity that carries it
+/// for as long as the attack runs, like a
+/// projectile or a laser's hitbox child. Every fixed tick each
+/// hitbox is checked against every hostile
+/// hurtbox, and each pair triggers one [`Attack`]
+/// and one [`Hit`], then waits out the hitbox's
+/// `rehit` before it can hit again.
+impl Plugin for HitboxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (expire_hitboxes, overlap_hitboxes)
+                .chain()
+                .in_set(HitDetection),
+        );
+    }
+}
+
+/// Where hitboxes are checked against hurtboxes.
+/// Anything moving a [`Hitbox`] should run before
+/// this.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HitDetection;
+
+/// The part of an entity that attacks can hit, as
+/// a circle on the ground plane
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Hurtbox {
+    pub radius: f32,
+}
+
+impl Default for Hurtbox {
+    fn default() -> Self {
+        Self { radius: 0.5 }
+    }
+}
+
+/// The area a [`Hitbox`] covers on the ground
+/// plane, from its entity's translation
+#[derive(Clone, Copy, Debug)]
+pub enum HitShape {
+    Circle {
+        radius: f32,
+    },
+    /// a beam of `width` running `length` along
+    /// `direction`
+    Ray {
+        direction: Dir2,
+        length: f32,
+        width: f32,
+    },
+}
+
+impl HitShape {
+    /// How far along the shape it first touches a
+    /// hurtbox, if it does
+    fn overlap(
+        self,
+        origin: Vec2,
+        hurtbox: &BoundingCircle,
+    ) -> Option<f32> {
+        match self {
+            HitShape::Circle { radius } => {
+                let distance =
+                    origin.distance(hurtbox.center);
+                (distance <= radius + hurtbox.radius())
+                    .then(|| {
+                        (distance - hurtbox.radius())
+                            .max(0.)
+                    })
+            }
+            HitShape::Ray {
+                direction,
+                length,
+                width,
+            } => RayCast2d::new(origin, direction, length)
+                .circle_intersection_at(
+                    &BoundingCircle::new(
+                        hurtbox.center,
+                        hurtbox.radius() + width / 2.,
+                    ),
+                ),
+        }
+    }
+}
+
+/// Damage dealt to every hostile [`Hurtbox`] that
+/// overlaps `shape`
+#[derive(Component, Clone, Debug)]
+#[require(Transform)]
+pub struct Hitbox {
+    /// who gets credit for the hit
+    pub attacker: Entity,
+    pub team: Team,
+    pub shape: HitShape,
+    pub strength: f32,
+    pub damage_type: DamageType,
+    /// status effects applied with each hit
+    pub effects: Vec<StatusEffect>,
+    /// despawns the hitbox when it runs out.
+    /// `None` lasts as long as the entity.
+    pub lifetime: Option<Timer>,
+    /// time before the same hurtbox can be hit
+    /// again. `None` hits each hurtbox once.
+    pub rehit: Option<Duration>,
+    /// keep hitting after the first hurtbox
+    pub pierce: bool,
+    hits: EntityHashMap<Timer>,
+}
+
+impl Hitbox {
+    pub fn new(
+        attacker: Entity,
+        team: Team,
+        shape: HitShape,
+        strength: f32,
+        damage_type: DamageType,
+    ) -> Self {
+        Self {
+            attacker,
+            team,
+            shape,
+            strength,
+            damage_type,
+            effects: Vec::new(),
+            lifetime: None,
+            rehit: None,
+            pierce: true,
+            hits: EntityHashMap::default(),
+        }
+    }
+
+    /// A hitbox that despawns after `duration`
+    pub fn lasting(self, duration: Duration) -> Self {
+        Self {
+            lifetime: Some(Timer::new(
+                duration,
+                TimerMode::Once,
+            )),
+            ..self
+        }
+    }
+
+    /// Forget who's been hit, so everything can
+    /// be hit again
+    pub fn reset_hits(&mut self) {
+        self.hits.clear();
+    }
+}
+
+/// Triggered on a [`Hitbox`] entity for each
+/// hurtbox it hits, alongside the [`Attack`]
+#[derive(EntityEvent)]
+pub struct Hit {
+    #[event_target]
+    pub hitbox: Entity,
+    pub receiver: Entity,
+    /// where the hitbox first touched the
+    /// receiver
+    pub point: Vec3,
+}
+
+fn expire_hitboxes(
+    mut hitboxes: Query<(Entity, &mut Hitbox)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut hitbox) in &mut hitboxes {
+        if hitbox.rehit.is_some() {
+            hitbox.hits.retain(|_, timer| {
+                !timer.tick(time.delta()).is_finished()
+            });
+        }
+        let Some(lifetime) = &mut hitbox.lifetime else {
+            continue;
+        };
+        if lifetime.tick(time.delta()).is_finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn overlap_hitboxes(
+    mut hitboxes: Query<(
+        Entity,
+        &mut Hitbox,
+        &GlobalTransform,
+    )>,
+    hurtboxes: Query<(
+        Entity,
+        &Hurtbox,
+        &GlobalTransform,
+        &Team,
+    )>,
+    mut commands: Commands,
+) {
+    for (entity, mut hitbox, transform) in &mut hitboxes {
+        let origin = transform.translation();
+        for (receiver, hurtbox, target, team) in &hurtboxes
+        {
+            if !hitbox.pierce && !hitbox.hits.is_empty() {
+                break;
+            }
+            if receiver == hitbox.attacker
+                || !hitbox.team.is_hostile_to(*team)
+                || hitbox.hits.contains_key(&receiver)
+            {
+                continue;
+            }
+            let Some(distance) = hitbox.shape.overlap(
+                origin.xz(),
+                &BoundingCircle::new(
+                    target.translation().xz(),
+                    hurtbox.radius,
+                ),
+            ) else {
+                continue;
+            };
+
+            let timer = Timer::new(
+                hitbox.rehit.unwrap_or_default(),
+                TimerMode::Once,
+            );
+            hitbox.hits.insert(receiver, timer);
+            commands.trigger(Attack {
+                attacker: hitbox.attacker,
+                receiver,
+                strength: hitbox.strength,
+                damage_type: hitbox.damage_type,
+                effects: hitbox.effects.clone(),
+            });
+
+            let toward = match hitbox.shape {
+                HitShape::Circle { .. } => {
+                    (target.translation().xz()
+                        - origin.xz())
+                    .normalize_or_zero()
+                }
+                HitShape::Ray { direction, .. } => {
+                    *direction
+                }
+            };
+            commands.trigger(Hit {
+                hitbox: entity,
+                receiver,
+                point: origin
+                    + (toward * distance).extend(0.).xzy(),
+            });
+        }
+    }
+}
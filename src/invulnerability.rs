@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use bevy::{color::palettes::tailwind::*, prelude::*};
+
+/// How long each on and off blink of a hit flash
+/// lasts
+const FLASH_INTERVAL: Duration = Duration::from_millis(80);
+
+pub struct InvulnerabilityPlugin;
+
+/// A window after taking damage where an entity
+/// can't be hurt again.
+///
+/// Entities with [`Invulnerability`] ignore every
+/// `Attack` for `window` after one lands, which
+/// `on_attack` checks before touching `Health`.
+/// While the window runs, the entity's model
+/// blinks between its own materials and a bright
+/// flash material.
+impl Plugin for InvulnerabilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HitFlashMaterial>()
+            .add_systems(FixedUpdate, tick_invulnerability)
+            .add_systems(Update, flash_invulnerable);
+    }
+}
+
+/// Hits ignored for a while after one lands.
+/// The window is per entity, half a second by
+/// default.
+#[derive(Component, Debug)]
+pub struct Invulnerability {
+    /// how long hits are ignored after one lands
+    pub window: Duration,
+    remaining: Duration,
+}
+
+impl Default for Invulnerability {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(500))
+    }
+}
+
+impl Invulnerability {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            remaining: Duration::ZERO,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.remaining.is_zero()
+    }
+
+    /// Start the window over
+    pub fn trigger(&mut self) {
+        self.remaining = self.window;
+    }
+}
+
+/// The material a mesh had before a hit flash
+/// replaced it
+#[derive(Component)]
+struct FlashedFrom(Handle<StandardMaterial>);
+
+#[derive(Resource)]
+struct HitFlashMaterial(Handle<StandardMaterial>);
+
+impl FromWorld for HitFlashMaterial {
+    fn from_world(world: &mut World) -> Self {
+        let color = LinearRgba::from(RED_100);
+        Self(
+            world
+                .resource_mut::<Assets<StandardMaterial>>()
+                .add(StandardMaterial {
+                    base_color: color.into(),
+                    emissive: color * 8.,
+                    unlit: true,
+                    ..default()
+                }),
+        )
+    }
+}
+
+fn tick_invulnerability(
+    mut query: Query<&mut Invulnerability>,
+    time: Res<Time>,
+) {
+    for mut invulnerability in &mut query {
+        if invulnerability.is_active() {
+            invulnerability.remaining = invulnerability
+                .remaining
+                .saturating_sub(time.delta());
+        }
+    }
+}
+
+fn flash_invulnerable(
+    query: Query<(Entity, &Invulnerability)>,
+    children: Query<&Children>,
+    mut meshes: Query<(
+        &mut MeshMaterial3d<StandardMaterial>,
+        Option<&FlashedFrom>,
+    )>,
+    flash: Res<HitFlashMaterial>,
+    mut commands: Commands,
+) {
+    for (entity, invulnerability) in &query {
+        let elapsed = invulnerability
+            .window
+            .saturating_sub(invulnerability.remaining);
+        let lit = invulnerability.is_active()
+            && (elapsed.as_millis()
+                / FLASH_INTERVAL.as_millis())
+                % 2
+                == 0;
+
+        for descendant in children.iter_descendants(entity)
+        {
+            let Ok((mut material, flashed_from)) =
+                meshes.get_mut(descendant)
+            else {
+                continue;
+            };
+            match (lit, flashed_from) {
+                (true, None) => {
+                    commands.entity(descendant).insert(
+                        FlashedFrom(material.0.clone()),
+                    );
+                    material.0 = flash.0.clone();
+                }
+                (false, Some(original)) => {
+                    material.0 = original.0.clone();
+                    commands
+                        .entity(descendant)
+                        .remove::<FlashedFrom>();
+                }
+                (true, Some(_)) | (false, None) => {}
+            }
+        }
+    }
+}
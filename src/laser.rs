@@ -1,11 +1,25 @@
-use bevy::prelude::*;
+use std::time::Duration;
+
+use bevy::{
+    color::palettes::tailwind::RED_400,
+    light::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::render_resource::AsBindGroup,
+    shader::ShaderRef,
+};
+
+use crate::{
+    health::{DamageType, Team},
+    hitbox::{Hit, HitDetection, HitShape, Hitbox},
+    line_of_sight::LineOfSight,
+};
 
 pub struct LaserPlugin;
 
 /// Laser process
 ///
-/// 1. `RunningLaserProcess` is added to an
-///    entity, with a target?
+/// 1. [`RunningLaserProcess`] is added to an
+///    entity, with a target
 /// 1. Show small laser as tracking indicator
 ///     - tracking indicator smooth_nudges
 ///       increasingly accurate until achieving
@@ -14,19 +28,528 @@ pub struct LaserPlugin;
 /// 3. firing laser is a distance-restricted beam
 ///     - inner color with rainbow fresnel oklch
 ///       hues
-/// 4. time
+/// 4. after `fire` time the beam shuts off and
+///    `RunningLaserProcess` is removed
 impl Plugin for LaserPlugin {
     fn build(&self, app: &mut App) {
-        app;
+        app.add_plugins(
+            MaterialPlugin::<LaserBeamMaterial>::default(),
+        )
+        .init_resource::<LaserBeamMesh>()
+        .add_systems(
+            FixedUpdate,
+            (run_laser_processes, laser_hits)
+                .chain()
+                .before(HitDetection),
+        )
+        .add_observer(on_laser_hit)
+        .add_observer(on_remove_laser_hit)
+        .add_observer(on_add_running_laser_process)
+        .add_observer(on_remove_running_laser_process);
+    }
+}
+
+/// A beam that hurts hostile entities it touches.
+///
+/// `reach` is worked out once per tick, and both
+/// the hit test and anything drawing the beam use
+/// it, so what you see is what hits you. The
+/// beam passes through every hostile entity it
+/// touches and stops at walls. Only entities with
+/// a [`Team`] fire, and the beam hits through a
+/// [`Hitbox`] on a [`LaserHitbox`] child of the
+/// firing entity.
+#[derive(Component, Clone, Debug)]
+pub struct LaserHit {
+    /// how far the beam goes when nothing is in
+    /// the way
+    pub length: f32,
+    pub width: f32,
+    pub damage: f32,
+    /// time between hits on the same target
+    pub cooldown: Duration,
+    /// which way the beam points. `None` follows
+    /// the entity's forward.
+    pub aim: Option<Vec3>,
+    reach: f32,
+}
+
+impl LaserHit {
+    pub fn new(
+        length: f32,
+        width: f32,
+        damage: f32,
+        cooldown: Duration,
+    ) -> Self {
+        Self {
+            length,
+            width,
+            damage,
+            cooldown,
+            aim: None,
+            reach: length,
+        }
+    }
+    /// How far the beam got this tick
+    pub fn reach(&self) -> f32 {
+        self.reach
+    }
+    /// Beams run along the ground plane
+    pub fn direction(
+        &self,
+        transform: &GlobalTransform,
+    ) -> Option<Dir2> {
+        Dir2::new(
+            self.aim
+                .unwrap_or(transform.forward().as_vec3())
+                .xz(),
+        )
+        .ok()
+    }
+    /// Where the beam ends, at a wall or at full
+    /// length
+    pub fn end(
+        &self,
+        transform: &GlobalTransform,
+    ) -> Option<Vec3> {
+        let direction = self.direction(transform)?;
+        Some(
+            transform.translation()
+                + (direction * self.reach).extend(0.).xzy(),
+        )
+    }
+}
+
+/// The child entity carrying a [`LaserHit`]'s
+/// [`Hitbox`], despawned when the laser stops
+#[derive(Component)]
+pub struct LaserHitbox;
+
+/// Triggered on an entity when a laser hits it
+#[derive(EntityEvent)]
+pub struct LaserImpact {
+    #[event_target]
+    pub receiver: Entity,
+    pub laser: Entity,
+    /// where the beam touched the receiver
+    pub point: Vec3,
+}
+
+/// Tuning for a [`RunningLaserProcess`]
+#[derive(Clone, Debug)]
+pub struct LaserConfig {
+    /// how long tracking takes to lock on
+    pub lock_on: Duration,
+    /// how long the beam fires after lock-on
+    pub fire: Duration,
+    pub length: f32,
+    pub width: f32,
+    /// damage dealt each `tick` the beam is on
+    /// a target
+    pub damage_per_tick: f32,
+    pub tick: Duration,
+    pub color: LinearRgba,
+}
+
+impl Default for LaserConfig {
+    fn default() -> Self {
+        Self {
+            lock_on: Duration::from_millis(1500),
+            fire: Duration::from_secs(1),
+            length: 8.,
+            width: 0.3,
+            damage_per_tick: 5.,
+            tick: Duration::from_millis(200),
+            color: RED_400.into(),
+        }
     }
 }
 
 /// Add this component to cause the entity to fire
 /// a laser at the target
 #[derive(Component)]
-struct RunningLaserProcess;
+pub struct RunningLaserProcess {
+    pub target: Entity,
+    pub config: LaserConfig,
+    phase: LaserPhase,
+    /// where the beam is pointing
+    aim: Vec3,
+    beam: Option<Entity>,
+}
+
+impl RunningLaserProcess {
+    pub fn new(
+        target: Entity,
+        config: LaserConfig,
+    ) -> Self {
+        Self {
+            phase: LaserPhase::Tracking(Timer::new(
+                config.lock_on,
+                TimerMode::Once,
+            )),
+            target,
+            config,
+            aim: Vec3::NEG_Z,
+            beam: None,
+        }
+    }
+    /// Has tracking locked on and the beam
+    /// started firing
+    pub fn is_firing(&self) -> bool {
+        matches!(self.phase, LaserPhase::Firing(_))
+    }
+}
+
+enum LaserPhase {
+    Tracking(Timer),
+    Firing(Timer),
+}
+
+/// The beam of a [`RunningLaserProcess`]
+#[derive(Component)]
+#[require(NotShadowCaster, NotShadowReceiver)]
+pub struct LaserBeam {
+    pub source: Entity,
+}
+
+#[derive(
+    Asset, TypePath, AsBindGroup, Debug, Clone, Default,
+)]
+pub struct LaserBeamMaterial {
+    /// 0 when tracking starts, 1 once locked on
+    #[uniform(0)]
+    pub lock: f32,
+    // Web examples WebGL2 support: structs must be 16 byte
+    // aligned.
+    #[cfg(feature = "webgl2")]
+    #[uniform(0)]
+    _webgl2_padding_8b: u32,
+    #[cfg(feature = "webgl2")]
+    #[uniform(0)]
+    _webgl2_padding_12b: u32,
+    #[cfg(feature = "webgl2")]
+    #[uniform(0)]
+    _webgl2_padding_16b: u32,
+    /// the inner color of the beam
+    #[uniform(0)]
+    pub color: LinearRgba,
+}
+
+impl Material for LaserBeamMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/laser_beam.wgsl".into()
+    }
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Add
+    }
+}
+
+/// The unit cylinder every beam is scaled from
+#[derive(Resource)]
+struct LaserBeamMesh(Handle<Mesh>);
+
+impl FromWorld for LaserBeamMesh {
+    fn from_world(world: &mut World) -> Self {
+        Self(
+            world
+                .resource_mut::<Assets<Mesh>>()
+                .add(Cylinder::new(0.5, 1.)),
+        )
+    }
+}
 
 fn on_add_running_laser_process(
-    _added: On<Add, RunningLaserProcess>,
+    added: On<Add, RunningLaserProcess>,
+    mut query: Query<(
+        &mut RunningLaserProcess,
+        &GlobalTransform,
+    )>,
+    targets: Query<&GlobalTransform>,
+    beam_mesh: Res<LaserBeamMesh>,
+    mut materials: ResMut<Assets<LaserBeamMaterial>>,
+    mut commands: Commands,
+) {
+    let Ok((mut process, transform)) =
+        query.get_mut(added.entity)
+    else {
+        return;
+    };
+
+    // start off roughly facing the target, tracking
+    // does the rest
+    let origin = transform.translation();
+    process.aim = targets
+        .get(process.target)
+        .ok()
+        .and_then(|target| {
+            (target.translation() - origin)
+                .with_y(0.)
+                .try_normalize()
+        })
+        .map(|aim| Quat::from_rotation_y(0.5).mul_vec3(aim))
+        .unwrap_or(transform.forward().as_vec3());
+
+    let beam = commands
+        .spawn((
+            Name::new("LaserBeam"),
+            LaserBeam {
+                source: added.entity,
+            },
+            Mesh3d(beam_mesh.0.clone()),
+            MeshMaterial3d(materials.add(
+                LaserBeamMaterial {
+                    lock: 0.,
+                    color: process.config.color,
+                    ..default()
+                },
+            )),
+            Transform::from_translation(origin)
+                .with_scale(Vec3::ZERO),
+        ))
+        .id();
+    process.beam = Some(beam);
+}
+
+fn on_remove_running_laser_process(
+    removed: On<Remove, RunningLaserProcess>,
+    query: Query<&RunningLaserProcess>,
+    mut commands: Commands,
+) {
+    let Some(beam) = query
+        .get(removed.entity)
+        .ok()
+        .and_then(|process| process.beam)
+    else {
+        return;
+    };
+    commands.entity(beam).try_despawn();
+    commands
+        .entity(removed.entity)
+        .try_remove::<LaserHit>();
+}
+
+fn run_laser_processes(
+    mut query: Query<(
+        Entity,
+        &mut RunningLaserProcess,
+        &GlobalTransform,
+        Option<&LaserHit>,
+    )>,
+    targets: Query<&GlobalTransform>,
+    mut beams: Query<
+        (
+            &mut Transform,
+            &MeshMaterial3d<LaserBeamMaterial>,
+        ),
+        With<LaserBeam>,
+    >,
+    mut materials: ResMut<Assets<LaserBeamMaterial>>,
+    line_of_sight: LineOfSight,
+    mut commands: Commands,
+    time: Res<Time>,
 ) {
+    let dt = time.delta_secs();
+    for (entity, mut process, transform, laser_hit) in
+        &mut query
+    {
+        let origin = transform.translation();
+        let target = targets.get(process.target).ok().map(
+            |target| target.translation().with_y(origin.y),
+        );
+
+        let process = &mut *process;
+        let lock = match &mut process.phase {
+            LaserPhase::Tracking(timer) => {
+                timer.tick(time.delta());
+                let lock = timer.fraction();
+                // tracking gets more accurate the closer
+                // it is to locking on
+                if let Some(direction) =
+                    target.and_then(|target| {
+                        (target - origin).try_normalize()
+                    })
+                {
+                    process.aim.smooth_nudge(
+                        &direction,
+                        1_f32.lerp(12., lock),
+                        dt,
+                    );
+                    process.aim = process.aim.normalize();
+                }
+                if timer.is_finished() {
+                    process.phase =
+                        LaserPhase::Firing(Timer::new(
+                            process.config.fire,
+                            TimerMode::Once,
+                        ));
+                    let config = &process.config;
+                    commands.entity(entity).insert(
+                        LaserHit {
+                            aim: Some(process.aim),
+                            ..LaserHit::new(
+                                config.length,
+                                config.width,
+                                config.damage_per_tick,
+                                config.tick,
+                            )
+                        },
+                    );
+                }
+                lock
+            }
+            LaserPhase::Firing(timer) => {
+                if timer.tick(time.delta()).is_finished() {
+                    commands
+                        .entity(entity)
+                        .remove::<RunningLaserProcess>();
+                    continue;
+                }
+                1.
+            }
+        };
+
+        // while firing the beam is drawn as far as it
+        // hits, the tracking indicator only needs to
+        // stop at walls
+        let length = match laser_hit {
+            Some(laser_hit) => laser_hit.reach(),
+            None => {
+                let Ok(direction) =
+                    Dir2::new(process.aim.xz())
+                else {
+                    continue;
+                };
+                line_of_sight.cast(
+                    origin.xz(),
+                    direction,
+                    process.config.length,
+                )
+            }
+        };
+
+        let Some((mut beam_transform, material)) = process
+            .beam
+            .and_then(|beam| beams.get_mut(beam).ok())
+        else {
+            continue;
+        };
+        // a thin tracking indicator until lock-on
+        let width = if process.is_firing() {
+            process.config.width
+        } else {
+            process.config.width * 0.2
+        };
+        *beam_transform = Transform {
+            translation: origin + process.aim * length / 2.,
+            rotation: Quat::from_rotation_arc(
+                Vec3::Y,
+                process.aim,
+            ),
+            scale: Vec3::new(width, length, width),
+        };
+        if let Some(mut material) =
+            materials.get_mut(&material.0)
+        {
+            material.lock = lock;
+        }
+    }
+}
+
+fn laser_hits(
+    mut lasers: Query<(
+        Entity,
+        &mut LaserHit,
+        &GlobalTransform,
+        &Team,
+        Option<&Children>,
+    )>,
+    mut hitboxes: Query<&mut Hitbox, With<LaserHitbox>>,
+    line_of_sight: LineOfSight,
+    mut commands: Commands,
+) {
+    for (entity, mut laser, transform, team, children) in
+        &mut lasers
+    {
+        let Some(direction) = laser.direction(transform)
+        else {
+            continue;
+        };
+        laser.reach = line_of_sight.cast(
+            transform.translation().xz(),
+            direction,
+            laser.length,
+        );
+
+        let shape = HitShape::Ray {
+            direction,
+            length: laser.reach,
+            width: laser.width,
+        };
+        let child = children.and_then(|children| {
+            children
+                .iter()
+                .find(|child| hitboxes.contains(*child))
+        });
+        match child {
+            Some(child) => {
+                let Ok(mut hitbox) =
+                    hitboxes.get_mut(child)
+                else {
+                    continue;
+                };
+                hitbox.shape = shape;
+                hitbox.strength = laser.damage;
+                hitbox.rehit = Some(laser.cooldown);
+            }
+            None => {
+                commands.spawn((
+                    Name::new("LaserHitbox"),
+                    LaserHitbox,
+                    Hitbox {
+                        rehit: Some(laser.cooldown),
+                        ..Hitbox::new(
+                            entity,
+                            *team,
+                            shape,
+                            laser.damage,
+                            DamageType::Laser,
+                        )
+                    },
+                    ChildOf(entity),
+                    // hits are checked before transforms
+                    // propagate, so start where the
+                    // laser is
+                    *transform,
+                ));
+            }
+        }
+    }
+}
+
+fn on_laser_hit(
+    hit: On<Hit>,
+    hitboxes: Query<&ChildOf, With<LaserHitbox>>,
+    mut commands: Commands,
+) {
+    let Ok(child_of) = hitboxes.get(hit.hitbox) else {
+        return;
+    };
+    commands.trigger(LaserImpact {
+        receiver: hit.receiver,
+        laser: child_of.parent(),
+        point: hit.point,
+    });
+}
+
+fn on_remove_laser_hit(
+    removed: On<Remove, LaserHit>,
+    children: Query<&Children>,
+    hitboxes: Query<(), With<LaserHitbox>>,
+    mut commands: Commands,
+) {
+    for child in children.iter_descendants(removed.entity) {
+        if hitboxes.contains(child) {
+            commands.entity(child).despawn();
+        }
+    }
 }
@@ -0,0 +1,517 @@
+use std::fmt;
+
+use bevy::{
+    color::palettes::tailwind::*, gltf::GltfMaterialName,
+    prelude::*, scene::SceneInstanceReady,
+};
+use bevy_blockout::UseBlockoutMaterial;
+
+use crate::{
+    CurrentLevel, EndGate, ExpectedEnemies, NumEnemies,
+    SpawnNEnemies,
+    assets::{GltfAssets, MyStates},
+    boss::is_boss_level,
+    navmesh::{NavMesh, ProcessedNavMesh},
+    player::{
+        PlayerCharacter, PlayerSpawnLocation, SpawnPlayer,
+    },
+    pool::{PooledScene, RecycleAndDespawn},
+    spawn_circle::{
+        InitSpawnCircle, SpawnSystems,
+        spawn_queue::{SpawnPriority, SpawnQueue},
+    },
+};
+
+pub struct LevelPlugin;
+
+/// Loading levels, and keeping entities from one
+/// level out of the next.
+///
+/// [`NextLevel`] cleans up the current level and
+/// spawns the next level's scene. Each
+/// [`LevelLoadStage`] is set by whatever finishes
+/// it: the scene's instance observer checks the
+/// scene and spawns the player, the player's
+/// scene being ready queues the enemies, and
+/// [`LevelReady`] is triggered once they're in
+/// the spawn queue. [`LevelLoadFailed`] is
+/// triggered if a stage can't be completed.
+///
+/// Every root entity with a `Transform` that
+/// shows up while a level is active gets tagged
+/// with [`LevelScoped`], and [`CleanupLevel`]
+/// despawns all of them when the level ends.
+/// Anything from an earlier level still around
+/// on the frame after that is reported as a
+/// leak.
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(MyStates::Next),
+            spawn_first_level,
+        )
+        .add_observer(to_next_level)
+        .add_observer(on_add_player_character)
+        .add_systems(
+            First,
+            report_leaked_entities
+                .run_if(resource_exists::<LeakCheck>),
+        )
+        .add_systems(Update, finish_level_load)
+        .add_systems(Last, tag_level_scoped);
+    }
+}
+
+/// The level an entity was spawned in. Despawned
+/// when that level ends.
+#[derive(Component, Debug)]
+pub struct LevelScoped(pub u32);
+
+/// Keeps an entity from being tagged
+/// [`LevelScoped`], for things that manage their
+/// own lifetime across levels, like pools.
+#[derive(Component, Default)]
+pub struct Persistent;
+
+/// Triggered during [`CleanupLevel`], before
+/// level scoped entities are despawned, so
+/// modules can reset anything that outlives a
+/// level.
+#[derive(Event)]
+pub struct LevelCleanup;
+
+fn tag_level_scoped(
+    query: Query<
+        Entity,
+        (
+            Added<Transform>,
+            Without<ChildOf>,
+            Without<LevelScoped>,
+            Without<Persistent>,
+            Without<PooledScene>,
+        ),
+    >,
+    current_level: Res<CurrentLevel>,
+    mut commands: Commands,
+) {
+    if current_level.0 == 0 {
+        return;
+    }
+    for entity in &query {
+        commands
+            .entity(entity)
+            .insert(LevelScoped(current_level.0));
+    }
+}
+
+/// Despawn everything that belongs to the current
+/// level.
+pub struct CleanupLevel;
+
+impl Command for CleanupLevel {
+    fn apply(self, world: &mut World) {
+        // anything spawned this frame hasn't been
+        // tagged yet
+        let _ = world.run_system_cached(tag_level_scoped);
+
+        world.trigger(LevelCleanup);
+
+        let scoped: Vec<Entity> = world
+            .query_filtered::<Entity, With<LevelScoped>>()
+            .iter(world)
+            .collect();
+        for entity in scoped {
+            RecycleAndDespawn(entity).apply(world);
+        }
+
+        world.insert_resource(LeakCheck);
+    }
+}
+
+/// Set by [`CleanupLevel`] to check for leaks at
+/// the start of the next frame, once anything
+/// the cleanup set off has run
+#[derive(Resource)]
+struct LeakCheck;
+
+fn report_leaked_entities(
+    query: Query<(Entity, &LevelScoped, Option<&Name>)>,
+    current_level: Res<CurrentLevel>,
+    mut commands: Commands,
+) {
+    commands.remove_resource::<LeakCheck>();
+
+    let leaked: Vec<String> = query
+        .iter()
+        .filter(|(_, scoped, _)| scoped.0 < current_level.0)
+        .map(|(entity, scoped, name)| {
+            format!(
+                "{entity} ({}) from level {}",
+                name.map(Name::as_str).unwrap_or("unnamed"),
+                scoped.0
+            )
+        })
+        .collect();
+
+    if !leaked.is_empty() {
+        error!(
+            ?leaked,
+            "entities leaked into level {}",
+            current_level.0
+        );
+    }
+    debug_assert!(
+        leaked.is_empty(),
+        "{} entities leaked out of their level",
+        leaked.len()
+    );
+}
+
+#[derive(Event)]
+pub struct NextLevel;
+
+/// Triggered once a level has made it through
+/// every [`LevelLoadStage`]
+#[derive(Event, Debug)]
+pub struct LevelReady {
+    pub level: u32,
+    pub root: Entity,
+}
+
+/// Triggered when a level can't finish loading
+#[derive(Event, Debug)]
+pub struct LevelLoadFailed {
+    pub level: u32,
+    /// the last stage that completed
+    pub stage: LevelLoadStage,
+    pub error: LevelLoadError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LevelLoadError {
+    /// nothing in the scene is marked as a
+    /// `NavMesh`
+    MissingNavMesh,
+    /// an entity marked as a `NavMesh` couldn't
+    /// be turned into one
+    InvalidNavMesh(Entity),
+    /// nothing in the scene is marked as a
+    /// `PlayerSpawnLocation`
+    MissingPlayerSpawn,
+}
+
+impl fmt::Display for LevelLoadError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            LevelLoadError::MissingNavMesh => {
+                write!(f, "level has no NavMesh")
+            }
+            LevelLoadError::InvalidNavMesh(entity) => {
+                write!(
+                    f,
+                    "NavMesh on {entity} wasn't processed"
+                )
+            }
+            LevelLoadError::MissingPlayerSpawn => {
+                write!(
+                    f,
+                    "level has no PlayerSpawnLocation"
+                )
+            }
+        }
+    }
+}
+
+/// Where a level is in the loading pipeline.
+/// Stages complete in order.
+#[derive(
+    Component,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+pub enum LevelLoadStage {
+    #[default]
+    Spawned,
+    SceneInstantiated,
+    NavMeshProcessed,
+    MarkersResolved,
+    PlayerSpawned,
+    EnemiesQueued,
+    Ready,
+}
+
+/// The root entity of a level's scene
+#[derive(Component)]
+#[require(LevelLoadStage)]
+pub struct LevelRoot {
+    pub level: u32,
+    /// how many enemies to queue once the level
+    /// is loaded
+    pub enemies: u32,
+}
+
+/// Run condition for systems that need a fully
+/// loaded level
+pub fn level_ready(
+    roots: Query<&LevelLoadStage, With<LevelRoot>>,
+) -> bool {
+    roots
+        .iter()
+        .any(|stage| *stage == LevelLoadStage::Ready)
+}
+
+fn spawn_first_level(mut commands: Commands) {
+    commands.trigger(NextLevel);
+}
+
+fn to_next_level(
+    _next: On<NextLevel>,
+    mut commands: Commands,
+    gltfs: Res<Assets<Gltf>>,
+    gltf: Res<GltfAssets>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut num_enemies: ResMut<NumEnemies>,
+    mut expected: ResMut<ExpectedEnemies>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+) {
+    commands.queue(CleanupLevel);
+    spawn_queue.clear();
+
+    current_level.0 += 1;
+
+    expected.seen_any = false;
+    let next_level = match current_level.0 {
+        1 => {
+            expected.expected = 0;
+            "level-001"
+        }
+        2 => {
+            num_enemies.0 += 1;
+            expected.expected = num_enemies.0;
+
+            "level-002"
+        }
+        _ => {
+            num_enemies.0 += 3;
+            expected.expected = num_enemies.0;
+            "Scene"
+        }
+    };
+
+    commands
+        .spawn((
+            Name::new(next_level),
+            LevelRoot {
+                level: current_level.0,
+                enemies: num_enemies.0,
+            },
+            SceneRoot(
+                gltfs.get(&gltf.misc).unwrap().named_scenes
+                    [next_level]
+                    .clone(),
+            ),
+        ))
+        .observe(load_level);
+}
+
+fn load_level(
+    ready: On<SceneInstanceReady>,
+    mut roots: Query<(&LevelRoot, &mut LevelLoadStage)>,
+    children: Query<&Children>,
+    navmeshes: Query<Has<ProcessedNavMesh>, With<NavMesh>>,
+    materials: Query<
+        &GltfMaterialName,
+        With<MeshMaterial3d<StandardMaterial>>,
+    >,
+    player_spawns: Query<(), With<PlayerSpawnLocation>>,
+    end_gates: Query<(), With<EndGate>>,
+    transform_helper: TransformHelper,
+    mut commands: Commands,
+) {
+    let Ok((root, mut stage)) = roots.get_mut(ready.entity)
+    else {
+        return;
+    };
+    let level = root.level;
+    let mut fail =
+        |stage: LevelLoadStage, error: LevelLoadError| {
+            error!(level, ?stage, %error, "failed to load level");
+            commands.trigger(LevelLoadFailed {
+                level,
+                stage,
+                error,
+            });
+        };
+    *stage = LevelLoadStage::SceneInstantiated;
+
+    // NavMesh entities are processed as they're
+    // added, so by now they should all have a
+    // ProcessedNavMesh
+    let mut found_navmesh = false;
+    for child in children.iter_descendants(ready.entity) {
+        let Ok(processed) = navmeshes.get(child) else {
+            continue;
+        };
+        if !processed {
+            fail(
+                *stage,
+                LevelLoadError::InvalidNavMesh(child),
+            );
+            return;
+        }
+        found_navmesh = true;
+    }
+    if !found_navmesh {
+        fail(*stage, LevelLoadError::MissingNavMesh);
+        return;
+    }
+    *stage = LevelLoadStage::NavMeshProcessed;
+
+    let blockout: Vec<Entity> = children
+        .iter_descendants(ready.entity)
+        .filter(|child| {
+            materials.get(*child).is_ok_and(|name| {
+                matches!(
+                    name.0.as_str(),
+                    "Floor" | "Plane.002" | "Plane.001"
+                )
+            })
+        })
+        .collect();
+    let Some(player_spawn) = children
+        .iter_descendants(ready.entity)
+        .find(|entity| player_spawns.contains(*entity))
+    else {
+        fail(
+            *stage,
+            LevelLoadError::MissingPlayerSpawn,
+        );
+        return;
+    };
+    let end_gate = children
+        .iter_descendants(ready.entity)
+        .find(|entity| end_gates.contains(*entity));
+    if end_gate.is_none() {
+        warn!(
+            level,
+            "level has no EndGate to leave through"
+        );
+    }
+    for entity in blockout {
+        commands.entity(entity).insert(UseBlockoutMaterial);
+    }
+    *stage = LevelLoadStage::MarkersResolved;
+
+    let Ok(player_spawn) = transform_helper
+        .compute_global_transform(player_spawn)
+    else {
+        warn!(level, "couldn't place the player spawn");
+        return;
+    };
+    commands.queue(SpawnPlayer {
+        position: player_spawn.compute_transform(),
+        remaining_health: None,
+    });
+}
+
+/// Wait for the player's scene before queueing
+/// the level's enemies
+fn on_add_player_character(
+    added: On<Add, PlayerCharacter>,
+    mut commands: Commands,
+) {
+    commands.entity(added.entity).observe(on_player_ready);
+}
+
+/// The level's player is in, so queue its
+/// enemies. [`SpawnNEnemies`] moves the level on
+/// to [`LevelLoadStage::EnemiesQueued`] once
+/// they're in the queue.
+fn on_player_ready(
+    _ready: On<SceneInstanceReady>,
+    mut roots: Query<(
+        Entity,
+        &LevelRoot,
+        &mut LevelLoadStage,
+    )>,
+    children: Query<&Children>,
+    end_gates: Query<(), With<EndGate>>,
+    transform_helper: TransformHelper,
+    spawn_systems: Res<SpawnSystems>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+    mut commands: Commands,
+) {
+    let Some((root, level_root, mut stage)) =
+        roots.iter_mut().find(|(_, _, stage)| {
+            **stage == LevelLoadStage::MarkersResolved
+        })
+    else {
+        return;
+    };
+    let level = level_root.level;
+    *stage = LevelLoadStage::PlayerSpawned;
+
+    // milestone levels put a boss in front of the
+    // way out
+    let end_gate = children
+        .iter_descendants(root)
+        .find(|entity| end_gates.contains(*entity));
+    if let Some(end_gate) =
+        end_gate.filter(|_| is_boss_level(level))
+    {
+        // a misplaced boss shouldn't hold up the
+        // rest of the level
+        match (
+            spawn_systems.0.get("boss"),
+            transform_helper
+                .compute_global_transform(end_gate),
+        ) {
+            (Some(boss), Ok(end_gate)) => spawn_queue.push(
+                SpawnPriority::High,
+                InitSpawnCircle {
+                    position: end_gate.translation().xz(),
+                    event: *boss,
+                    spawn_color: PURPLE_400.into(),
+                },
+            ),
+            (None, _) => {
+                warn!(
+                    level,
+                    "no spawn system registered for the boss"
+                );
+            }
+            (_, Err(error)) => {
+                warn!(level, %error, "couldn't place the boss");
+            }
+        }
+    }
+    commands.trigger(SpawnNEnemies(level_root.enemies));
+}
+
+/// Everything's queued, so the level can start
+fn finish_level_load(
+    mut roots: Query<
+        (Entity, &LevelRoot, &mut LevelLoadStage),
+        Changed<LevelLoadStage>,
+    >,
+    mut commands: Commands,
+) {
+    for (root, level_root, mut stage) in &mut roots {
+        if *stage != LevelLoadStage::EnemiesQueued {
+            continue;
+        }
+        let level = level_root.level;
+        info!(level, "level ready");
+        *stage = LevelLoadStage::Ready;
+        commands.trigger(LevelReady { level, root });
+    }
+}
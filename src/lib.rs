@@ -1,22 +1,21 @@
-use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+use std::{
+    f32::consts::{FRAC_PI_2, FRAC_PI_4},
+    time::Duration,
+};
 
 use bevy::{
     animation::AnimationEvent,
     color::palettes::tailwind::*,
     ecs::entity::EntityHashSet,
-    gltf::GltfMaterialName,
     input::common_conditions::input_toggle_active,
     light::{VolumetricLight, light_consts::lux},
     math::{
-        bounding::{
-            Aabb2d, BoundingCircle, IntersectsVolume,
-        },
+        bounding::{Aabb2d, IntersectsVolume},
         sampling::UniformMeshSampler,
     },
     prelude::*,
-    scene::SceneInstanceReady,
 };
-use bevy_blockout::{BlockoutPlugin, UseBlockoutMaterial};
+use bevy_blockout::BlockoutPlugin;
 use bevy_enhanced_input::prelude::*;
 #[cfg(feature = "inspector_egui")]
 use bevy_inspector_egui::{
@@ -34,41 +33,77 @@ use rand::{
 };
 
 use crate::{
+    affix::AffixPlugin,
     animation_extension::GltfExtensionHandlerAnimationPlugin,
+    archetype::ArchetypePlugin,
     assets::{GltfAssets, JamAssetsPlugin, MyStates},
     atmosphere::DefaultAtmosphere,
-    crystals::CrystalPlugin,
-    eyes::EyeBallPlugin,
+    boss::{Boss, BossPlugin},
+    crystals::{CrystalPlugin, CrystalPylon},
+    death::DeathPlugin,
+    director::{DirectorPlugin, SpawnDirector},
+    enemy::{EnemyBehavior, EnemyPlugin, Knockback},
     flock_sphere::FlockSpherePlugin,
     hammer_smack::{
         HammerSmack, HammerSmackMaterial, HammerSmackPlugin,
     },
-    health::{Attack, Health, HealthPlugin},
+    health::{DamageType, Health, HealthPlugin, Team},
+    hitbox::{
+        Hit, HitShape, Hitbox, HitboxPlugin, Hurtbox,
+    },
+    invulnerability::InvulnerabilityPlugin,
+    level::{
+        LevelLoadError, LevelLoadFailed, LevelLoadStage,
+        LevelPlugin, LevelRoot, NextLevel, level_ready,
+    },
+    line_of_sight::LineOfSightPlugin,
     navmesh::{NavMeshPlugin, ProcessedNavMesh},
     player::{
         PlayerCharacter, PlayerPlugin, PlayerSpawnLocation,
-        SpawnPlayer,
     },
+    pool::{PoolPlugin, RecycleAndDespawn},
+    projectile::{ProjectilePlugin, ReflectProjectiles},
+    separation::SeparationPlugin,
     spawn_circle::{
-        InitSpawnCircle, SpawnSystems,
+        InitSpawnCircle, SpawnCircle, SpawnSystems,
+        spawn_queue::{
+            SpawnBudget, SpawnPriority, SpawnQueue,
+        },
         spawn_systems::{ScaleIn, TranslateUpIn},
     },
+    status::{StatusEffect, StatusKind, StatusPlugin},
+    telegraph::TelegraphPlugin,
 };
 
+pub mod affix;
 pub mod animation_extension;
+pub mod archetype;
 pub mod assets;
 pub mod atmosphere;
 pub mod awareness;
+pub mod boss;
 pub mod controls;
 pub mod crystals;
+pub mod death;
+pub mod director;
+pub mod enemy;
 pub mod eyes;
 pub mod flock_sphere;
 pub mod hammer_smack;
 pub mod health;
+pub mod hitbox;
+pub mod invulnerability;
 pub mod laser;
+pub mod level;
+pub mod line_of_sight;
 pub mod navmesh;
 pub mod player;
+pub mod pool;
+pub mod projectile;
+pub mod separation;
 pub mod spawn_circle;
+pub mod status;
+pub mod telegraph;
 
 #[cfg(feature = "free_camera")]
 pub mod debug_free_cam;
@@ -133,13 +168,29 @@ pub fn app() -> App {
             JamAssetsPlugin,
             NavMeshPlugin,
             FlockSpherePlugin,
-            EyeBallPlugin,
+            ArchetypePlugin,
             CrystalPlugin,
             HealthPlugin,
             PlayerPlugin,
             GltfExtensionHandlerAnimationPlugin,
             HammerSmackPlugin,
         ))
+        .add_plugins((
+            DirectorPlugin,
+            PoolPlugin,
+            LevelPlugin,
+            EnemyPlugin,
+            BossPlugin,
+            SeparationPlugin,
+            ProjectilePlugin,
+            AffixPlugin,
+            DeathPlugin,
+            TelegraphPlugin,
+            HitboxPlugin,
+            InvulnerabilityPlugin,
+            StatusPlugin,
+            LineOfSightPlugin,
+        ))
         .add_systems(Startup, startup)
         // .add_systems(Update, |mut gizmos: Gizmos| {
         //     gizmos.circle(
@@ -157,19 +208,18 @@ pub fn app() -> App {
             FixedUpdate,
             (
                 random_spawn_eyes.run_if(
-                    in_state(MyStates::Next).and(
-                        resource_equals(RandomSpawn(true)),
-                    ),
+                    in_state(MyStates::Next)
+                        .and(level_ready)
+                        .and(resource_equals(RandomSpawn(
+                            true,
+                        ))),
+                ),
+                test_end_gate.run_if(
+                    in_state(MyStates::Next)
+                        .and(level_ready),
                 ),
-                test_end_gate
-                    .run_if(in_state(MyStates::Next)),
             ),
         )
-        .add_systems(
-            OnEnter(MyStates::Next),
-            spawn_first_level,
-        )
-        .add_observer(on_scene_spawn_player)
         .add_systems(
             OnExit(MyStates::AssetLoading),
             on_exit_asset_loading,
@@ -179,113 +229,37 @@ pub fn app() -> App {
             |mut despawnable: ResMut<Despawnable>,
              mut commands: Commands| {
                 for entity in despawnable.0.drain() {
-                    commands.entity(entity).try_despawn();
+                    commands
+                        .queue(RecycleAndDespawn(entity));
                 }
             },
         )
         .add_observer(on_hammer_slam_finished)
         .add_observer(on_hammer_slam_hit)
-        .add_observer(to_next_level);
+        .add_observer(on_hammer_hitbox_hit);
 
     app
 }
 
-#[derive(Event)]
-struct NextLevel;
-
 #[derive(Event)]
 struct SpawnNEnemies(u32);
 
-fn spawn_first_level(mut commands: Commands) {
-    commands.trigger(NextLevel);
-}
-fn to_next_level(
-    next: On<NextLevel>,
-    query: Query<
-        Entity,
-        Or<(With<PlayerCharacter>, With<SceneRoot>)>,
-    >,
-    mut commands: Commands,
-    gltfs: Res<Assets<Gltf>>,
-    gltf: Res<GltfAssets>,
-    mut current_level: ResMut<CurrentLevel>,
-    mut num_enemies: ResMut<NumEnemies>,
-    mut expected: ResMut<ExpectedEnemies>,
-) {
-    for entity in &query {
-        commands.entity(entity).try_despawn();
-    }
-
-    current_level.0 += 1;
-
-    expected.seen_any = false;
-    let next_level = match current_level.0 {
-        1 => {
-            expected.expected = 0;
-            "level-001"
-        }
-        2 => {
-            num_enemies.0 += 1;
-            expected.expected = num_enemies.0;
-
-            "level-002"
-        }
-        _ => {
-            num_enemies.0 += 3;
-            expected.expected = num_enemies.0;
-            "Scene"
-        }
-    };
-
-    commands
-        .spawn(SceneRoot(
-            gltfs.get(&gltf.misc).unwrap().named_scenes
-                [next_level]
-                .clone(),
-        ))
-        .observe(
-            |ready: On<SceneInstanceReady>,
-             children: Query<&Children>,
-             query: Query<(
-                &GltfMaterialName,
-                &MeshMaterial3d<StandardMaterial>,
-            )>,
-             mut commands: Commands,
-             num_enemies: Res<NumEnemies>| {
-                for child in
-                    children.iter_descendants(ready.entity)
-                {
-                    if let Ok((name, _material)) =
-                        query.get(child)
-                    {
-                        match name.0.as_str() {
-                            "Floor" | "Plane.002"
-                            | "Plane.001" => {
-                                commands
-                                    .entity(child)
-                                    .insert(
-                                        UseBlockoutMaterial,
-                                    );
-                            }
-                            name => {
-                                info!(?name);
-                            }
-                        };
-                    };
-                }
-
-                commands
-                    .trigger(SpawnNEnemies(num_enemies.0));
-            },
-        );
-}
-
 fn on_hammer_slam_finished(
     finished: On<HammerSlamFinished>,
 ) {
     // info!("DONE");
     // play idle, remove spam prevention
 }
+
+/// Knockback speed at the center of a hammer
+/// impact
+const HAMMER_KNOCKBACK: f32 = 6.;
+const HAMMER_STUN: Duration = Duration::from_millis(600);
+const HAMMER_RADIUS: f32 = 1.5;
+/// How long a hammer impact can hit things
+const HAMMER_HIT_WINDOW: Duration =
+    Duration::from_millis(100);
+
 fn on_hammer_slam_hit(
     finished: On<HammerSlamHit>,
     players: Query<
@@ -295,10 +269,6 @@ fn on_hammer_slam_hit(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<HammerSmackMaterial>>,
-    enemies: Query<
-        (Entity, &Transform),
-        (With<Health>, Without<PlayerCharacter>),
-    >,
 ) {
     let Ok((player_entity, player)) = players.single()
     else {
@@ -313,35 +283,91 @@ fn on_hammer_slam_hit(
 
     commands.spawn((
         Name::new("hammer_hit_effect"),
-        Mesh3d(meshes.add(
-            Circle::new(1.5).mesh().build().rotated_by(
-                Quat::from_rotation_x(-FRAC_PI_2),
+        Mesh3d(
+            meshes.add(
+                Circle::new(HAMMER_RADIUS)
+                    .mesh()
+                    .build()
+                    .rotated_by(Quat::from_rotation_x(
+                        -FRAC_PI_2,
+                    )),
             ),
-        )),
+        ),
         MeshMaterial3d(materials.add(
             HammerSmackMaterial { smack_percent: 0. },
         )),
         HammerSmack::default(),
         new_transform,
     ));
-    let hit_circle = BoundingCircle {
+    commands.spawn((
+        Name::new("hammer_hitbox"),
+        HammerHitbox,
+        Hitbox {
+            effects: vec![StatusEffect::new(
+                StatusKind::Stun,
+                0.,
+                HAMMER_STUN,
+            )],
+            ..Hitbox::new(
+                player_entity,
+                Team::Player,
+                HitShape::Circle {
+                    radius: HAMMER_RADIUS,
+                },
+                20.,
+                DamageType::Blunt,
+            )
+        }
+        .lasting(HAMMER_HIT_WINDOW),
+        Transform::from_translation(
+            new_transform.translation,
+        ),
+    ));
+    commands.trigger(ReflectProjectiles {
         center: new_transform.translation.xz(),
-        circle: Circle { radius: 1.5 },
+        radius: HAMMER_RADIUS,
+        by: player_entity,
+    });
+}
+
+/// The [`Hitbox`] of a hammer impact
+#[derive(Component)]
+struct HammerHitbox;
+
+fn on_hammer_hitbox_hit(
+    hit: On<Hit>,
+    hitboxes: Query<&GlobalTransform, With<HammerHitbox>>,
+    // only enemies that move get pushed around
+    hurtboxes: Query<
+        (&GlobalTransform, &Hurtbox),
+        (
+            With<EnemyBehavior>,
+            Without<Boss>,
+            Without<CrystalPylon>,
+        ),
+    >,
+    mut commands: Commands,
+) {
+    let (Ok(hitbox), Ok((target, hurtbox))) = (
+        hitboxes.get(hit.hitbox),
+        hurtboxes.get(hit.receiver),
+    ) else {
+        return;
     };
-    for (entity, enemy) in enemies {
-        let enemy = BoundingCircle {
-            center: enemy.translation.xz(),
-            circle: Circle::new(0.5),
-        };
-
-        if enemy.intersects(&hit_circle) {
-            commands.trigger(Attack {
-                attacker: player_entity,
-                receiver: entity,
-                strength: 20.,
-            });
-        }
-    }
+
+    // pushed away from the impact, harder closer to
+    // the center
+    let offset = target.translation().xz()
+        - hitbox.translation().xz();
+    let falloff = 1.
+        - (offset.length()
+            / (HAMMER_RADIUS + hurtbox.radius))
+            .clamp(0., 1.);
+    commands.entity(hit.receiver).insert(Knockback {
+        velocity: offset.normalize_or_zero()
+            * HAMMER_KNOCKBACK
+            * falloff,
+    });
 }
 
 #[derive(Component, Reflect)]
@@ -361,9 +387,13 @@ fn test_end_gate(
     mut commands: Commands,
     num_enemies: Res<NumEnemies>,
     mut expected: ResMut<ExpectedEnemies>,
+    spawn_queue: Res<SpawnQueue>,
+    spawn_circles: Query<(), With<SpawnCircle>>,
 ) {
     if expected.expected == 0
         || (enemies.iter().count() == 0
+            && spawn_queue.is_empty()
+            && spawn_circles.is_empty()
             && expected.seen_any)
     {
         for mut vis in &mut visible {
@@ -418,30 +448,6 @@ fn on_exit_asset_loading(
     clip.add_event(duration, HammerSlamFinished);
 }
 
-fn on_scene_spawn_player(
-    ready: On<SceneInstanceReady>,
-    query: Query<
-        &GlobalTransform,
-        With<PlayerSpawnLocation>,
-    >,
-    children: Query<&Children>,
-    mut commands: Commands,
-    transform_helper: TransformHelper,
-) {
-    let Some(entity) = children
-        .iter_descendants(ready.entity)
-        .find(|entity| query.get(*entity).is_ok())
-    else {
-        return;
-    };
-    let player_spawn = transform_helper
-        .compute_global_transform(entity)
-        .unwrap();
-    commands.queue(SpawnPlayer {
-        position: player_spawn.compute_transform(),
-        remaining_health: None,
-    });
-}
 fn pointer_click_spawn_eye(
     mut picked: On<Pointer<Click>>,
     mut commands: Commands,
@@ -469,17 +475,6 @@ fn pointer_click_spawn_eye(
     // });
 }
 
-#[derive(Component)]
-struct TestSpawnTimer(Timer);
-impl Default for TestSpawnTimer {
-    fn default() -> Self {
-        Self(Timer::from_seconds(
-            5.,
-            TimerMode::Repeating,
-        ))
-    }
-}
-
 #[derive(Component)]
 pub struct ActivePlayerCamera;
 
@@ -527,14 +522,15 @@ fn startup(mut commands: Commands) {
 }
 
 fn random_spawn_eyes(
-    mut commands: Commands,
     mut rng: Single<&mut WyRand, With<GlobalRng>>,
-    mut timer: Local<TestSpawnTimer>,
+    mut director: ResMut<SpawnDirector>,
     time: Res<Time>,
     spawn_systems: Res<SpawnSystems>,
     current_navmesh: Query<(&ProcessedNavMesh, &Mesh3d)>,
     meshes: Res<Assets<Mesh>>,
     navmeshes: Res<Assets<vleue_navigator::NavMesh>>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+    budget: Res<SpawnBudget>,
 ) {
     let Ok((navmesh, mesh)) = current_navmesh.single()
     else {
@@ -551,50 +547,72 @@ fn random_spawn_eyes(
     )
     .unwrap();
 
-    if timer.0.tick(time.delta()).just_finished() {
+    if director.tick(time.delta()) {
+        // don't let ambient spawns pile up while the
+        // budget is full
+        if spawn_queue.len() >= budget.max_queued {
+            return;
+        }
         let sample = rng.sample(&sampler);
         // TODO: loop until finding a valid position in the navmesh.
         // but for now we're using the mesh to sample so it *should* always
         // find a valid location
         if navmesh.transformed_is_in_mesh(sample.with_y(0.))
         {
-            let enemy_to_spawn =
-                ["eye", "flock-sphere", "gem-rock"]
-                    .choose(&mut rng)
-                    .unwrap();
-            let id = spawn_systems
-                .0
-                .get(*enemy_to_spawn)
-                .expect("enemy {enemy_to_spawn} should have a valid spawn system registered");
-
-            commands.queue(InitSpawnCircle {
-                position: sample.xz(),
-                event: *id,
-                spawn_color: RED_400.into(),
-            });
+            let Some(enemy_to_spawn) =
+                director.choose_enemy(&mut rng)
+            else {
+                warn!("director has no enemies to spawn");
+                return;
+            };
+            let Some(id) =
+                spawn_systems.0.get(enemy_to_spawn)
+            else {
+                warn!(
+                    enemy_to_spawn,
+                    "no spawn system registered for enemy"
+                );
+                return;
+            };
+
+            spawn_queue.push(
+                SpawnPriority::Low,
+                InitSpawnCircle {
+                    position: sample.xz(),
+                    event: *id,
+                    spawn_color: RED_400.into(),
+                },
+            );
         }
     }
 }
 
-#[derive(Component)]
-struct MoveRandomly {
-    from: Vec2,
-    to: Vec2,
-}
-
 fn on_spawn_n_enemies(
     spawn: On<SpawnNEnemies>,
-    mut commands: Commands,
+    mut spawn_queue: ResMut<SpawnQueue>,
     mut rng: Single<&mut WyRand, With<GlobalRng>>,
     spawn_systems: Res<SpawnSystems>,
     current_navmesh: Query<(&ProcessedNavMesh, &Mesh3d)>,
     meshes: Res<Assets<Mesh>>,
     navmeshes: Res<Assets<vleue_navigator::NavMesh>>,
+    mut levels: Query<(&LevelRoot, &mut LevelLoadStage)>,
+    mut commands: Commands,
 ) {
     let spawn_count = spawn.0;
+    let mut loading =
+        levels.iter_mut().filter(|(_, stage)| {
+            **stage == LevelLoadStage::PlayerSpawned
+        });
 
     let Ok((navmesh, mesh)) = current_navmesh.single()
     else {
+        if let Some((root, stage)) = loading.next() {
+            commands.trigger(LevelLoadFailed {
+                level: root.level,
+                stage: *stage,
+                error: LevelLoadError::MissingNavMesh,
+            });
+        }
         return;
     };
 
@@ -619,16 +637,29 @@ fn on_spawn_n_enemies(
                 ["eye", "flock-sphere", "gem-rock"]
                     .choose(&mut rng)
                     .unwrap();
-            let id = spawn_systems
-                .0
-                .get(*enemy_to_spawn)
-                .expect("enemy {enemy_to_spawn} should have a valid spawn system registered");
-
-            commands.queue(InitSpawnCircle {
-                position: sample.xz(),
-                event: *id,
-                spawn_color: RED_400.into(),
-            });
+            let Some(id) =
+                spawn_systems.0.get(*enemy_to_spawn)
+            else {
+                warn!(
+                    enemy_to_spawn,
+                    "no spawn system registered for enemy"
+                );
+                continue;
+            };
+
+            spawn_queue.push(
+                SpawnPriority::High,
+                InitSpawnCircle {
+                    position: sample.xz(),
+                    event: *id,
+                    spawn_color: RED_400.into(),
+                },
+            );
         }
     }
+    // a loading level waits on its enemies being
+    // in the queue
+    for (_, mut stage) in loading {
+        *stage = LevelLoadStage::EnemiesQueued;
+    }
 }
@@ -0,0 +1,167 @@
+use bevy::{
+    ecs::system::SystemParam,
+    math::bounding::{BoundingCircle, RayCast2d},
+    prelude::*,
+};
+
+use crate::navmesh::CurrentNavMesh;
+
+/// Distance between navmesh samples along a sight
+/// line
+const SAMPLE_STEP: f32 = 0.25;
+/// Level nodes that get an [`Occluder`], and how
+/// far around them sight is blocked
+const OCCLUDING_NODES: &[(&str, f32)] =
+    &[("arch", 2.), ("arch.001", 2.), ("arch.002", 2.)];
+
+pub struct LineOfSightPlugin;
+
+/// Gives the level pieces in [`OCCLUDING_NODES`]
+/// an [`Occluder`] as their scene spawns them.
+impl Plugin for LineOfSightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(insert_occluders);
+    }
+}
+
+/// Blocks line of sight in a circle around an
+/// entity on the ground plane, for level pieces
+/// like `arch` that don't cut a hole in the
+/// navmesh.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[type_path = "api"]
+pub struct Occluder {
+    pub radius: f32,
+}
+
+/// Line of sight on the ground plane.
+///
+/// Sight is blocked by anything that isn't
+/// navmesh, such as walls and pillars, and by
+/// [`Occluder`]s.
+#[derive(SystemParam)]
+pub struct LineOfSight<'w, 's> {
+    navmesh: CurrentNavMesh<'w, 's>,
+    occluders: Query<
+        'w,
+        's,
+        (
+            &'static Occluder,
+            &'static GlobalTransform,
+        ),
+    >,
+}
+
+impl LineOfSight<'_, '_> {
+    /// Can `from` see `to`
+    pub fn clear(&self, from: Vec2, to: Vec2) -> bool {
+        let Ok(direction) = Dir2::new(to - from) else {
+            return true;
+        };
+        let distance = from.distance(to);
+        self.cast(from, direction, distance) >= distance
+    }
+
+    /// How far a ray gets before it's blocked, up
+    /// to `max_distance`. Navmesh edges are found
+    /// to within `SAMPLE_STEP`.
+    pub fn cast(
+        &self,
+        origin: Vec2,
+        direction: Dir2,
+        max_distance: f32,
+    ) -> f32 {
+        let mut distance = max_distance;
+
+        let ray =
+            RayCast2d::new(origin, direction, max_distance);
+        for (occluder, transform) in &self.occluders {
+            let center = transform.translation().xz();
+            // an occluder doesn't block sight from
+            // inside itself
+            if center.distance(origin) <= occluder.radius {
+                continue;
+            }
+            if let Some(hit) = ray.circle_intersection_at(
+                &BoundingCircle::new(
+                    center,
+                    occluder.radius,
+                ),
+            ) {
+                distance = distance.min(hit);
+            }
+        }
+
+        if let Some(navmesh) = self.navmesh.get() {
+            let mut travelled = SAMPLE_STEP;
+            while travelled < distance {
+                let point = origin + direction * travelled;
+                if !navmesh.transformed_is_in_mesh(
+                    point.extend(0.).xzy(),
+                ) {
+                    return travelled;
+                }
+                travelled += SAMPLE_STEP;
+            }
+        }
+
+        distance
+    }
+}
+
+fn insert_occluders(
+    added: On<Add, Name>,
+    names: Query<&Name>,
+    mut commands: Commands,
+) {
+    let Ok(name) = names.get(added.entity) else {
+        return;
+    };
+    let Some((_, radius)) = OCCLUDING_NODES
+        .iter()
+        .find(|(node, _)| *node == name.as_str())
+    else {
+        return;
+    };
+    commands
+        .entity(added.entity)
+        .insert(Occluder { radius: *radius });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn arch_blocks_sight_through_it() {
+        let mut world = World::new();
+        world.init_resource::<Assets<vleue_navigator::NavMesh>>();
+        world.add_observer(insert_occluders);
+        world.spawn((
+            Name::new("arch.001"),
+            GlobalTransform::default(),
+        ));
+        world.flush();
+
+        world
+            .run_system_once(
+                |line_of_sight: LineOfSight| {
+                    // eye on one side, player on the
+                    // other
+                    assert!(!line_of_sight.clear(
+                        Vec2::new(-5., 0.),
+                        Vec2::new(5., 0.),
+                    ));
+                    // walking out from behind it
+                    assert!(line_of_sight.clear(
+                        Vec2::new(-5., 3.),
+                        Vec2::new(5., 3.),
+                    ));
+                },
+            )
+            .unwrap();
+    }
+}
@@ -1,4 +1,6 @@
-use bevy::prelude::*;
+use std::collections::VecDeque;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
 use vleue_navigator::VleueNavigatorPlugin;
 
 pub struct NavMeshPlugin;
@@ -13,13 +15,83 @@ impl Plugin for NavMeshPlugin {
 #[derive(Component, Reflect)]
 #[reflect(Component)]
 #[type_path = "api"]
-struct NavMesh;
+pub struct NavMesh;
 
 #[derive(Component)]
 pub struct ProcessedNavMesh(
     pub Handle<vleue_navigator::NavMesh>,
 );
 
+/// The current level's navmesh, for systems that
+/// need to ask it questions
+#[derive(SystemParam)]
+pub struct CurrentNavMesh<'w, 's> {
+    processed: Query<'w, 's, &'static ProcessedNavMesh>,
+    navmeshes: Res<'w, Assets<vleue_navigator::NavMesh>>,
+}
+
+impl CurrentNavMesh<'_, '_> {
+    /// `None` if there is no navmesh, or if two
+    /// levels overlap during a transition
+    pub fn get(&self) -> Option<&vleue_navigator::NavMesh> {
+        let processed = self.processed.single().ok()?;
+        self.navmeshes.get(&processed.0)
+    }
+    /// Is a point on the ground plane inside the
+    /// navmesh
+    pub fn contains(&self, point: Vec2) -> bool {
+        self.get().is_some_and(|navmesh| {
+            navmesh.transformed_is_in_mesh(
+                point.extend(0.).xzy(),
+            )
+        })
+    }
+    /// The part of `step` that can be taken from
+    /// `from` without leaving the navmesh,
+    /// sliding along edges. Anything goes
+    /// without a navmesh.
+    pub fn slide(
+        &self,
+        from: Vec2,
+        step: Vec2,
+    ) -> Option<Vec2> {
+        if self.get().is_none() {
+            return Some(step);
+        }
+        [step, step.with_y(0.), step.with_x(0.)]
+            .into_iter()
+            .find(|step| self.contains(from + step))
+    }
+    /// Waypoints on the ground plane from `from`
+    /// to `to`, ending at `to`
+    pub fn path(
+        &self,
+        from: Vec2,
+        to: Vec2,
+    ) -> Option<VecDeque<Vec2>> {
+        let path = self.get()?.transformed_path(
+            from.extend(0.).xzy(),
+            to.extend(0.).xzy(),
+        )?;
+        Some(
+            path.path
+                .iter()
+                .map(|point| point.xz())
+                .collect(),
+        )
+    }
+}
+
+/// Waypoints an entity is following across the
+/// navmesh
+#[derive(Component, Debug)]
+pub struct NavPath {
+    /// the goal the waypoints were planned for
+    pub goal: Vec2,
+    /// next waypoint first
+    pub waypoints: VecDeque<Vec2>,
+}
+
 fn on_add_navmesh(
     added: On<Add, NavMesh>,
     query: Query<&Mesh3d>,
@@ -4,8 +4,12 @@ use bevy::prelude::*;
 
 use crate::{
     assets::GltfAssets,
+    awareness::Detectable,
     controls::ControlledByPlayer,
-    health::Health,
+    health::{Health, Team},
+    hitbox::Hurtbox,
+    invulnerability::Invulnerability,
+    separation::BodyRadius,
     spawn_circle::spawn_systems::{ScaleIn, TranslateUpIn},
 };
 
@@ -20,7 +24,14 @@ impl Plugin for PlayerPlugin {
 #[derive(Component, Reflect)]
 #[reflect(Component)]
 #[type_path = "api"]
-#[require(ControlledByPlayer)]
+#[require(
+    ControlledByPlayer,
+    Detectable,
+    Team = Team::Player,
+    BodyRadius = BodyRadius(0.4),
+    Hurtbox = Hurtbox { radius: 0.4 },
+    Invulnerability
+)]
 pub struct PlayerCharacter;
 
 #[derive(Component, Reflect)]
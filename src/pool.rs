@@ -0,0 +1,200 @@
+use bevy::{platform::collections::HashMap, prelude::*};
+
+use crate::assets::{GltfAssets, MyStates};
+
+/// How many instances of each enemy scene to
+/// create before the first level starts
+const WARM_POOLS: &[(&str, usize)] =
+    &[("Eye", 6), ("flock-sphere", 8), ("gem-rock", 3)];
+
+pub struct PoolPlugin;
+
+/// Keeps instantiated glTF scenes around so
+/// spawning an enemy doesn't have to instantiate
+/// a whole scene.
+///
+/// Enemies hold their model as a child with
+/// [`PooledScene`]. Despawning an enemy with
+/// [`RecycleAndDespawn`] detaches the model and
+/// puts it back in the pool for the next spawn.
+impl Plugin for PoolPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScenePool>().add_systems(
+            OnEnter(MyStates::Next),
+            warm_scene_pools,
+        );
+    }
+}
+
+/// Idle scene instances, by glTF scene name
+#[derive(Resource, Default)]
+pub struct ScenePool(HashMap<String, Vec<Entity>>);
+
+/// A scene instance that goes back into the
+/// [`ScenePool`] instead of being despawned
+#[derive(Component)]
+pub struct PooledScene(pub String);
+
+/// A [`PooledScene`] that is currently sitting in
+/// the pool
+#[derive(Component)]
+pub struct InPool;
+
+fn warm_scene_pools(
+    mut commands: Commands,
+    mut pool: ResMut<ScenePool>,
+    gltf: Res<GltfAssets>,
+    gltfs: Res<Assets<Gltf>>,
+) {
+    let Some(gltf) = gltfs.get(&gltf.misc) else {
+        warn!("can't warm scene pools without the glTF");
+        return;
+    };
+    for (scene, count) in WARM_POOLS {
+        let Some(handle) = gltf.named_scenes.get(*scene)
+        else {
+            warn!(
+                scene,
+                "can't warm pool for missing scene"
+            );
+            continue;
+        };
+        let idle =
+            pool.0.entry(scene.to_string()).or_default();
+        for _ in 0..*count {
+            idle.push(
+                commands
+                    .spawn((
+                        Name::new(format!(
+                            "Pooled {scene}"
+                        )),
+                        PooledScene(scene.to_string()),
+                        InPool,
+                        SceneRoot(handle.clone()),
+                        Transform::default(),
+                        Visibility::Hidden,
+                    ))
+                    .id(),
+            );
+        }
+    }
+}
+
+/// Give `parent` an instance of `scene` as a
+/// child, reusing one from the [`ScenePool`] if
+/// there is one.
+pub struct AttachPooledScene {
+    pub parent: Entity,
+    pub scene: String,
+}
+
+impl Command for AttachPooledScene {
+    fn apply(self, world: &mut World) {
+        if world.get_entity(self.parent).is_err() {
+            return;
+        }
+
+        let reused = loop {
+            let Some(candidate) = world
+                .resource_mut::<ScenePool>()
+                .0
+                .get_mut(&self.scene)
+                .and_then(Vec::pop)
+            else {
+                break None;
+            };
+            // pooled scenes can be despawned out from
+            // under us, so skip anything that's gone
+            if world.get_entity(candidate).is_ok() {
+                break Some(candidate);
+            }
+        };
+
+        let model = match reused {
+            Some(model) => model,
+            None => {
+                let Some(scene) = world
+                    .resource::<Assets<Gltf>>()
+                    .get(
+                        &world
+                            .resource::<GltfAssets>()
+                            .misc,
+                    )
+                    .and_then(|gltf| {
+                        gltf.named_scenes
+                            .get(self.scene.as_str())
+                    })
+                    .cloned()
+                else {
+                    warn!(
+                        scene = self.scene.as_str(),
+                        "can't attach missing scene"
+                    );
+                    return;
+                };
+                world
+                    .spawn((
+                        Name::new(format!(
+                            "Pooled {}",
+                            self.scene
+                        )),
+                        PooledScene(self.scene),
+                        SceneRoot(scene),
+                    ))
+                    .id()
+            }
+        };
+
+        world.entity_mut(model).remove::<InPool>().insert(
+            (
+                Transform::IDENTITY,
+                Visibility::Inherited,
+                ChildOf(self.parent),
+            ),
+        );
+    }
+}
+
+/// Despawn an entity, returning any
+/// [`PooledScene`]s in its hierarchy to the
+/// [`ScenePool`] first.
+pub struct RecycleAndDespawn(pub Entity);
+
+impl Command for RecycleAndDespawn {
+    fn apply(self, world: &mut World) {
+        let mut stack = vec![self.0];
+        let mut models = vec![];
+        while let Some(entity) = stack.pop() {
+            let Ok(entity_ref) = world.get_entity(entity)
+            else {
+                continue;
+            };
+            if let Some(pooled) =
+                entity_ref.get::<PooledScene>()
+            {
+                models.push((entity, pooled.0.clone()));
+                continue;
+            }
+            if let Some(children) =
+                entity_ref.get::<Children>()
+            {
+                stack.extend(children.iter());
+            }
+        }
+
+        for (model, scene) in models {
+            world
+                .entity_mut(model)
+                .remove::<ChildOf>()
+                .insert((InPool, Visibility::Hidden));
+            world
+                .resource_mut::<ScenePool>()
+                .0
+                .entry(scene)
+                .or_default()
+                .push(model);
+        }
+
+        let _ = world.try_despawn(self.0);
+    }
+}
@@ -0,0 +1,512 @@
+use std::time::Duration;
+
+use bevy::{
+    color::palettes::tailwind::*,
+    platform::collections::HashMap, prelude::*,
+};
+
+use crate::{
+    health::{DamageType, Team},
+    hitbox::{Hit, HitDetection, HitShape, Hitbox},
+    navmesh::CurrentNavMesh,
+    status::StatusEffect,
+    telegraph::{
+        GroundTelegraph, TelegraphOf, TelegraphShape,
+        Telegraphs,
+    },
+};
+
+/// How much wider than the projectile a lobbed
+/// shot's landing hits
+const SPLASH_SCALE: f32 = 4.;
+/// How long a lobbed shot's landing can hit
+/// things
+const SPLASH_WINDOW: Duration = Duration::from_millis(100);
+
+pub struct ProjectilePlugin;
+
+/// Projectiles that travel instead of hitting
+/// instantly.
+///
+/// [`FireProjectile`] spawns a [`Projectile`]
+/// from any entity with a [`Team`]. Straight
+/// shots fly level, optionally homing in on their
+/// target, and carry a [`Hitbox`] that hits the
+/// first hostile body they touch. Arcing shots
+/// are lobbed at where the target stood, mark
+/// their landing spot with a [`GroundTelegraph`],
+/// and leave a splash [`Hitbox`] there when they
+/// come down.
+///
+/// [`ReflectProjectiles`] turns projectiles
+/// around and hands them to the other team, which
+/// is how a hammer slam bats them back.
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ProjectileAssets>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    move_projectiles,
+                    land_projectiles,
+                    telegraph_landings,
+                )
+                    .chain()
+                    .before(HitDetection),
+            )
+            .add_observer(on_reflect_projectiles)
+            .add_observer(on_projectile_hit);
+    }
+}
+
+/// Tuning for a [`Projectile`]
+#[derive(Clone, Debug)]
+pub struct ProjectileConfig {
+    /// units per second along the ground
+    pub speed: f32,
+    pub lifetime: Duration,
+    /// how fast a straight projectile turns
+    /// towards its target, in radians per second
+    pub homing: f32,
+    pub damage: f32,
+    pub damage_type: DamageType,
+    /// status effects applied with each hit
+    pub effects: Vec<StatusEffect>,
+    pub radius: f32,
+    /// peak height of a lobbed projectile. `None`
+    /// flies straight.
+    pub arc: Option<f32>,
+    pub color: LinearRgba,
+}
+
+impl Default for ProjectileConfig {
+    fn default() -> Self {
+        Self {
+            speed: 4.,
+            lifetime: Duration::from_secs(3),
+            homing: 0.,
+            damage: 10.,
+            damage_type: DamageType::Energy,
+            effects: Vec::new(),
+            radius: 0.15,
+            arc: None,
+            color: ORANGE_400.into(),
+        }
+    }
+}
+
+/// Meshes and materials shared by every
+/// projectile with the same radius and color
+#[derive(Resource)]
+struct ProjectileAssets(
+    HashMap<
+        (u32, [u32; 4]),
+        (Handle<Mesh>, Handle<StandardMaterial>),
+    >,
+);
+
+impl FromWorld for ProjectileAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut assets = Self(HashMap::default());
+        assets.get(world, &ProjectileConfig::default());
+        assets
+    }
+}
+
+impl ProjectileAssets {
+    fn get(
+        &mut self,
+        world: &mut World,
+        config: &ProjectileConfig,
+    ) -> (Handle<Mesh>, Handle<StandardMaterial>) {
+        let key = (
+            config.radius.to_bits(),
+            config.color.to_f32_array().map(f32::to_bits),
+        );
+        self.0
+            .entry(key)
+            .or_insert_with(|| {
+                let mesh = world
+                    .resource_mut::<Assets<Mesh>>()
+                    .add(Sphere::new(config.radius));
+                let material = world
+                    .resource_mut::<Assets<StandardMaterial>>()
+                    .add(StandardMaterial {
+                        base_color: config.color.into(),
+                        emissive: config.color * 4.,
+                        unlit: true,
+                        ..default()
+                    });
+                (mesh, material)
+            })
+            .clone()
+    }
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    /// who gets credit for the hit
+    pub attacker: Entity,
+    pub team: Team,
+    pub target: Option<Entity>,
+    pub config: ProjectileConfig,
+    motion: Motion,
+    lifetime: Timer,
+}
+
+impl Projectile {
+    /// How close a body has to be to get hit
+    fn reach(&self) -> f32 {
+        match self.motion {
+            Motion::Straight { .. } => self.config.radius,
+            Motion::Arc { .. } => {
+                self.config.radius * SPLASH_SCALE
+            }
+        }
+    }
+}
+
+enum Motion {
+    Straight { direction: Dir2 },
+    Arc { from: Vec3, to: Vec2, flight: Timer },
+}
+
+impl Motion {
+    fn new(
+        config: &ProjectileConfig,
+        from: Vec3,
+        to: Vec2,
+    ) -> Self {
+        match config.arc {
+            Some(_) => Motion::Arc {
+                from,
+                to,
+                flight: Timer::from_seconds(
+                    from.xz().distance(to) / config.speed,
+                    TimerMode::Once,
+                ),
+            },
+            None => Motion::Straight {
+                direction: Dir2::new(to - from.xz())
+                    .unwrap_or(Dir2::X),
+            },
+        }
+    }
+}
+
+/// Fire a projectile from `origin` at `target`.
+/// The projectile fights for the `attacker`'s
+/// [`Team`].
+pub struct FireProjectile {
+    pub attacker: Entity,
+    pub origin: Vec3,
+    pub target: Entity,
+    pub config: ProjectileConfig,
+}
+
+impl Command for FireProjectile {
+    fn apply(self, world: &mut World) {
+        let Some(team) =
+            world.get::<Team>(self.attacker).copied()
+        else {
+            warn!(
+                attacker = %self.attacker,
+                "projectile fired by an entity without a Team"
+            );
+            return;
+        };
+        let Some(target) = world
+            .get::<GlobalTransform>(self.target)
+            .map(|target| target.translation().xz())
+        else {
+            return;
+        };
+
+        let (mesh, material) = world.resource_scope(
+            |world, mut assets: Mut<ProjectileAssets>| {
+                assets.get(world, &self.config)
+            },
+        );
+
+        let motion =
+            Motion::new(&self.config, self.origin, target);
+        let hitbox = match motion {
+            Motion::Straight { .. } => Some(Hitbox {
+                pierce: false,
+                effects: self.config.effects.clone(),
+                ..Hitbox::new(
+                    self.attacker,
+                    team,
+                    HitShape::Circle {
+                        radius: self.config.radius,
+                    },
+                    self.config.damage,
+                    self.config.damage_type,
+                )
+            }),
+            // lobbed shots only hit where they land
+            Motion::Arc { .. } => None,
+        };
+
+        let mut projectile = world.spawn((
+            Name::new("Projectile"),
+            Projectile {
+                attacker: self.attacker,
+                team,
+                target: Some(self.target),
+                motion,
+                lifetime: Timer::new(
+                    self.config.lifetime,
+                    TimerMode::Once,
+                ),
+                config: self.config,
+            },
+            Mesh3d(mesh),
+            MeshMaterial3d(material),
+            Transform::from_translation(self.origin),
+        ));
+        if let Some(hitbox) = hitbox {
+            projectile.insert(hitbox);
+        }
+    }
+}
+
+/// Turn around every projectile within `radius`
+/// of `center` that's hostile to `by`, sending it
+/// back at whoever fired it
+#[derive(Event)]
+pub struct ReflectProjectiles {
+    pub center: Vec2,
+    pub radius: f32,
+    pub by: Entity,
+}
+
+fn on_reflect_projectiles(
+    reflect: On<ReflectProjectiles>,
+    mut projectiles: Query<(
+        &mut Projectile,
+        &Transform,
+        Option<&mut Hitbox>,
+    )>,
+    teams: Query<&Team>,
+    transforms: Query<&GlobalTransform>,
+) {
+    let Ok(team) = teams.get(reflect.by) else {
+        return;
+    };
+    for (mut projectile, transform, hitbox) in
+        &mut projectiles
+    {
+        let position = transform.translation.xz();
+        if !projectile.team.is_hostile_to(*team)
+            || position.distance(reflect.center)
+                > reflect.radius + projectile.config.radius
+        {
+            continue;
+        }
+
+        let shooter = projectile.attacker;
+        // send it back where it came from, or
+        // straight away from the reflector if the
+        // shooter is gone
+        let to = transforms
+            .get(shooter)
+            .map(|shooter| shooter.translation().xz())
+            .unwrap_or(
+                position + (position - reflect.center),
+            );
+        projectile.motion = Motion::new(
+            &projectile.config,
+            transform.translation,
+            to,
+        );
+        projectile.attacker = reflect.by;
+        projectile.team = *team;
+        projectile.target =
+            transforms.contains(shooter).then_some(shooter);
+        projectile.lifetime.reset();
+        if let Some(mut hitbox) = hitbox {
+            hitbox.attacker = reflect.by;
+            hitbox.team = *team;
+            hitbox.reset_hits();
+        }
+    }
+}
+
+fn move_projectiles(
+    mut projectiles: Query<(
+        Entity,
+        &mut Projectile,
+        &mut Transform,
+    )>,
+    targets: Query<&GlobalTransform>,
+    navmesh: CurrentNavMesh,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    let dt = time.delta_secs();
+    for (entity, mut projectile, mut transform) in
+        &mut projectiles
+    {
+        if projectile
+            .lifetime
+            .tick(time.delta())
+            .just_finished()
+        {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let target = projectile
+            .target
+            .and_then(|target| targets.get(target).ok())
+            .map(|target| target.translation().xz());
+        let Projectile { config, motion, .. } =
+            &mut *projectile;
+        match motion {
+            Motion::Straight { direction } => {
+                let position = transform.translation.xz();
+                if let Some(wanted) =
+                    target.and_then(|target| {
+                        Dir2::new(target - position).ok()
+                    })
+                {
+                    let turn =
+                        direction.angle_to(*wanted).clamp(
+                            -config.homing * dt,
+                            config.homing * dt,
+                        );
+                    *direction =
+                        Rot2::radians(turn) * *direction;
+                }
+                let next = position
+                    + *direction * config.speed * dt;
+                // walls stop straight shots
+                if navmesh.get().is_some()
+                    && !navmesh.contains(next)
+                {
+                    commands.entity(entity).despawn();
+                    continue;
+                }
+                transform.translation.x = next.x;
+                transform.translation.z = next.y;
+            }
+            Motion::Arc { from, to, flight } => {
+                flight.tick(time.delta());
+                let t = flight.fraction();
+                let ground = from.xz().lerp(*to, t);
+                let height = config.arc.unwrap_or_default();
+                transform.translation = ground
+                    .extend(
+                        from.y * (1. - t)
+                            + height * 4. * t * (1. - t),
+                    )
+                    .xzy();
+            }
+        }
+    }
+}
+
+/// Lobbed shots splash down once their flight
+/// is over
+fn land_projectiles(
+    projectiles: Query<(Entity, &Projectile, &Transform)>,
+    mut commands: Commands,
+) {
+    for (entity, projectile, transform) in &projectiles {
+        let Motion::Arc { flight, .. } = &projectile.motion
+        else {
+            continue;
+        };
+        if !flight.is_finished() {
+            continue;
+        }
+        commands.spawn((
+            Name::new("ProjectileSplash"),
+            Hitbox {
+                effects: projectile.config.effects.clone(),
+                ..Hitbox::new(
+                    projectile.attacker,
+                    projectile.team,
+                    HitShape::Circle {
+                        radius: projectile.reach(),
+                    },
+                    projectile.config.damage,
+                    projectile.config.damage_type,
+                )
+            }
+            .lasting(SPLASH_WINDOW),
+            Transform::from_translation(
+                transform.translation,
+            ),
+        ));
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Straight shots stop at the first body they hit
+fn on_projectile_hit(
+    hit: On<Hit>,
+    projectiles: Query<(), With<Projectile>>,
+    mut commands: Commands,
+) {
+    if projectiles.contains(hit.hitbox) {
+        commands.entity(hit.hitbox).despawn();
+    }
+}
+
+/// Mark where lobbed projectiles will land with
+/// a [`GroundTelegraph`] that fills in as they
+/// come down, following them if they're
+/// reflected
+fn telegraph_landings(
+    projectiles: Query<(
+        Entity,
+        &Projectile,
+        Option<&Telegraphs>,
+    )>,
+    mut telegraphs: Query<(
+        &mut GroundTelegraph,
+        &mut Transform,
+    )>,
+    mut commands: Commands,
+) {
+    for (entity, projectile, landing) in &projectiles {
+        let Motion::Arc { to, flight, .. } =
+            &projectile.motion
+        else {
+            continue;
+        };
+        if flight.is_finished() {
+            continue;
+        }
+        let translation = to.extend(0.02).xzy();
+        let Some(landing) = landing else {
+            commands.spawn((
+                Name::new("LandingTelegraph"),
+                GroundTelegraph {
+                    lead: flight.clone(),
+                    ..GroundTelegraph::new(
+                        TelegraphShape::Circle {
+                            radius: projectile.reach(),
+                        },
+                        flight.duration(),
+                        RED_400.with_alpha(0.6),
+                    )
+                },
+                Transform::from_translation(translation),
+                TelegraphOf(entity),
+            ));
+            continue;
+        };
+        for telegraph in landing.iter() {
+            let Ok((mut telegraph, mut transform)) =
+                telegraphs.get_mut(telegraph)
+            else {
+                continue;
+            };
+            telegraph.lead = flight.clone();
+            transform.translation = translation;
+        }
+    }
+}
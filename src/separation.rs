@@ -0,0 +1,103 @@
+use bevy::prelude::*;
+
+use crate::{
+    enemy::EnemyUpdate, navmesh::CurrentNavMesh,
+    player::PlayerCharacter,
+};
+
+pub struct SeparationPlugin;
+
+/// Keeps bodies from overlapping on the ground
+/// plane.
+///
+/// Two overlapping enemies are pushed apart
+/// evenly. The player is body-blocked: when the
+/// player overlaps an enemy, only the player
+/// moves, so they can't walk through or shove
+/// enemies. [`Immovable`] bodies never move, so
+/// whatever overlaps them takes the whole push.
+impl Plugin for SeparationPlugin {
+    fn build(&self, app: &mut App) {
+        // player movement comes from input, which
+        // is handled before the fixed schedule runs
+        app.add_systems(
+            FixedUpdate,
+            separate_bodies.after(EnemyUpdate),
+        );
+    }
+}
+
+/// The size of an entity's body on the ground
+/// plane
+#[derive(Component, Clone, Copy, Debug)]
+pub struct BodyRadius(pub f32);
+
+impl Default for BodyRadius {
+    fn default() -> Self {
+        Self(0.5)
+    }
+}
+
+/// A body that separation never moves, like a
+/// pylon
+#[derive(Component, Default)]
+pub struct Immovable;
+
+fn separate_bodies(
+    mut bodies: Query<(
+        &BodyRadius,
+        &mut Transform,
+        Has<PlayerCharacter>,
+        Has<Immovable>,
+    )>,
+    navmesh: CurrentNavMesh,
+) {
+    let mut pairs = bodies.iter_combinations_mut();
+    while let Some(
+        [
+            (a_radius, mut a, a_is_player, a_is_fixed),
+            (b_radius, mut b, b_is_player, b_is_fixed),
+        ],
+    ) = pairs.fetch_next()
+    {
+        if a_is_fixed && b_is_fixed {
+            continue;
+        }
+        let offset =
+            b.translation.xz() - a.translation.xz();
+        let overlap =
+            a_radius.0 + b_radius.0 - offset.length();
+        if overlap <= 0. {
+            continue;
+        }
+        // bodies on the same spot still need a
+        // direction to split along
+        let direction =
+            offset.try_normalize().unwrap_or(Vec2::X);
+
+        // how much of the overlap each side resolves
+        let (a_share, b_share) = match (
+            a_is_fixed,
+            b_is_fixed,
+            a_is_player,
+            b_is_player,
+        ) {
+            (true, _, _, _) => (0., 1.),
+            (_, true, _, _) => (1., 0.),
+            (_, _, true, false) => (1., 0.),
+            (_, _, false, true) => (0., 1.),
+            _ => (0.5, 0.5),
+        };
+        for (transform, push) in [
+            (&mut a, -direction * overlap * a_share),
+            (&mut b, direction * overlap * b_share),
+        ] {
+            if let Some(step) = navmesh
+                .slide(transform.translation.xz(), push)
+            {
+                transform.translation +=
+                    step.extend(0.).xzy();
+            }
+        }
+    }
+}
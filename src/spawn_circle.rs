@@ -10,16 +10,22 @@ use bevy::{
     shader::ShaderRef,
 };
 
-use crate::assets::{GltfAssets, MyStates};
+use crate::{
+    assets::{GltfAssets, MyStates},
+    level::{LevelCleanup, Persistent},
+};
 
+pub mod spawn_queue;
 pub mod spawn_systems;
 pub struct SpawnCirclePlugin;
 
 impl Plugin for SpawnCirclePlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<SpawnSystems>()
+            .init_resource::<SpawnCirclePool>()
             .add_plugins((
                 spawn_systems::SpawnSystemsPlugin,
+                spawn_queue::SpawnQueuePlugin,
                 MaterialPlugin::<
                     ExtendedMaterial<
                         StandardMaterial,
@@ -43,15 +49,23 @@ impl Plugin for SpawnCirclePlugin {
                 )
                     .run_if(in_state(MyStates::Next)),
             )
-            .add_observer(on_add_cylinder_material);
+            .add_observer(on_add_cylinder_material)
+            .add_observer(recycle_spawn_circles_on_cleanup);
     }
     fn finish(&self, app: &mut App) {
         let handle = app
             .world_mut()
             .resource_mut::<Assets<Mesh>>()
             .add(Plane3d::new(Vec3::Y, Vec2::ONE));
+        let texture = app
+            .world()
+            .resource::<AssetServer>()
+            .load("spawn-circle-001.png");
         app.world_mut().insert_resource(
-            SpawnCircleMeshPlane { default: handle },
+            SpawnCircleMeshPlane {
+                default: handle,
+                texture,
+            },
         );
     }
 }
@@ -59,8 +73,31 @@ impl Plugin for SpawnCirclePlugin {
 #[derive(Resource)]
 struct SpawnCircleMeshPlane {
     default: Handle<Mesh>,
+    texture: Handle<Image>,
+}
+
+/// Spawn circles that finished their animation
+/// and are waiting to be reused by
+/// [`InitSpawnCircle`]
+#[derive(Resource, Default)]
+struct SpawnCirclePool(Vec<Entity>);
+
+/// The parts of a spawn circle that get reset
+/// when it is reused
+#[derive(Component)]
+struct SpawnCircleParts {
+    base: Entity,
+    material: Handle<
+        ExtendedMaterial<StandardMaterial, SpawnCircleExt>,
+    >,
 }
 
+/// The column scenes spawned by
+/// [`spawn_cylinder`], kept around for the next
+/// time the circle is used
+#[derive(Component)]
+struct SpawnCircleColumns([Entity; 2]);
+
 #[derive(Component)]
 pub struct SpawnCircleBase;
 
@@ -202,18 +239,64 @@ fn spawn_cylinder(
         Entity,
         &mut CylinderTimer,
         &Transform,
+        Option<&SpawnCircleColumns>,
     )>,
     time: Res<Time>,
     mut commands: Commands,
     gltf: Res<GltfAssets>,
     gltfs: Res<Assets<Gltf>>,
+    children: Query<&Children>,
+    column_materials: Query<
+        &MeshMaterial3d<
+            ExtendedMaterial<
+                StandardMaterial,
+                SpawnColumnExt,
+            >,
+        >,
+    >,
+    mut materials: ResMut<
+        Assets<
+            ExtendedMaterial<
+                StandardMaterial,
+                SpawnColumnExt,
+            >,
+        >,
+    >,
 ) {
-    for (entity, mut timer, _transform) in &mut query {
+    for (entity, mut timer, _transform, columns) in
+        &mut query
+    {
         if timer.0.tick(time.delta()).just_finished() {
             commands
                 .entity(entity)
                 .remove::<CylinderTimer>();
 
+            // a reused circle already has its columns,
+            // they only need to be shown and restarted
+            if let Some(columns) = columns {
+                for column in columns.0 {
+                    commands
+                        .entity(column)
+                        .insert(Visibility::Inherited);
+                    for descendant in
+                        children.iter_descendants(column)
+                    {
+                        let Ok(handle) = column_materials
+                            .get(descendant)
+                        else {
+                            continue;
+                        };
+                        if let Some(material) =
+                            materials.get_mut(&handle.0)
+                        {
+                            material.extension.spawn_time =
+                                time.elapsed_secs();
+                        }
+                    }
+                }
+                continue;
+            }
+
             let child = commands
                 .spawn((
                     Name::new("CylinderScene"),
@@ -242,7 +325,10 @@ fn spawn_cylinder(
                 .id();
             commands
                 .entity(entity)
-                .add_children(&[child, child2]);
+                .add_children(&[child, child2])
+                .insert(SpawnCircleColumns([
+                    child, child2,
+                ]));
         };
     }
 }
@@ -315,18 +401,72 @@ fn spawn_circle_despawn(
     mut query: Query<(
         Entity,
         &mut SpawnCircleDespawnTimer,
+        Option<&SpawnCircleColumns>,
     )>,
     time: Res<Time>,
     mut commands: Commands,
+    mut pool: ResMut<SpawnCirclePool>,
 ) {
-    for (entity, mut timer) in &mut query {
+    for (entity, mut timer, columns) in &mut query {
         if timer.0.tick(time.delta()).just_finished() {
+            recycle_spawn_circle(
+                &mut commands,
+                &mut pool,
+                entity,
+                columns,
+            );
+        }
+    }
+}
+
+/// Circles that are still running when a level
+/// ends go straight back into the pool without
+/// spawning anything
+fn recycle_spawn_circles_on_cleanup(
+    _cleanup: On<LevelCleanup>,
+    query: Query<
+        (Entity, Option<&SpawnCircleColumns>),
+        With<SpawnCircle>,
+    >,
+    mut commands: Commands,
+    mut pool: ResMut<SpawnCirclePool>,
+) {
+    for (entity, columns) in &query {
+        recycle_spawn_circle(
+            &mut commands,
+            &mut pool,
+            entity,
+            columns,
+        );
+    }
+}
+
+/// Hide the circle and keep it around for the
+/// next [`InitSpawnCircle`]
+fn recycle_spawn_circle(
+    commands: &mut Commands,
+    pool: &mut SpawnCirclePool,
+    entity: Entity,
+    columns: Option<&SpawnCircleColumns>,
+) {
+    commands
+        .entity(entity)
+        .remove::<(
+            SpawnCircle,
+            CylinderTimer,
+            SpawnCircleSpawnTimer,
+            SpawnCircleDespawnTimer,
+            SpawnEventToTrigger,
+        )>()
+        .insert(Visibility::Hidden);
+    if let Some(columns) = columns {
+        for column in columns.0 {
             commands
-                .entity(entity)
-                .remove::<SpawnCircleSpawnTimer>();
-            commands.entity(entity).despawn();
+                .entity(column)
+                .insert(Visibility::Hidden);
         }
     }
+    pool.0.push(entity);
 }
 
 #[derive(Component)]
@@ -340,14 +480,67 @@ pub struct InitSpawnCircle {
 
 impl Command for InitSpawnCircle {
     fn apply(self, world: &mut World) {
+        let time = world.resource::<Time>().elapsed_secs();
+        let transform = Transform::from_xyz(
+            self.position.x,
+            0.001,
+            self.position.y,
+        );
+
+        // reuse a finished circle if there is one
+        while let Some(entity) =
+            world.resource_mut::<SpawnCirclePool>().0.pop()
+        {
+            let Some(parts) = world
+                .get_entity(entity)
+                .ok()
+                .and_then(|entity| {
+                    entity.get::<SpawnCircleParts>()
+                })
+            else {
+                continue;
+            };
+            let base = parts.base;
+            let material_handle = parts.material.clone();
+
+            if let Some(material) = world
+                .resource_mut::<Assets<
+                    ExtendedMaterial<
+                        StandardMaterial,
+                        SpawnCircleExt,
+                    >,
+                >>()
+                .get_mut(&material_handle)
+            {
+                material.extension.spawn_time = time;
+                material.extension.spawn_color =
+                    self.spawn_color;
+            }
+            if let Ok(mut base) = world.get_entity_mut(base)
+            {
+                base.insert(
+                    Transform::default().with_scale(
+                        Vec3::new(0.8, 1., 0.8),
+                    ),
+                );
+            }
+            world.entity_mut(entity).insert((
+                SpawnCircle,
+                SpawnEventToTrigger(self.event),
+                Visibility::Visible,
+                transform,
+            ));
+            return;
+        }
+
         let spawn_circle_texture = world
-            .resource_mut::<AssetServer>()
-            .load("spawn-circle-001.png");
+            .resource::<SpawnCircleMeshPlane>()
+            .texture
+            .clone();
         let spawn_circle_mesh_plane = world
             .resource::<SpawnCircleMeshPlane>()
             .default
             .clone();
-        let time = world.resource::<Time>().elapsed_secs();
 
         let material = world
             .resource_mut::<Assets<
@@ -370,26 +563,34 @@ impl Command for InitSpawnCircle {
                 },
             });
 
-        world.spawn((
-            Name::new("SpawnCircle"),
-            SpawnCircle,
-            SpawnEventToTrigger(self.event),
-            Visibility::Visible,
-            Transform::from_xyz(
-                self.position.x,
-                0.001,
-                self.position.y,
-            ),
-            children![(
+        let circle = world
+            .spawn((
+                Name::new("SpawnCircle"),
+                SpawnCircle,
+                // recycled through SpawnCirclePool
+                // instead of being despawned with the
+                // level
+                Persistent,
+                SpawnEventToTrigger(self.event),
+                Visibility::Visible,
+                transform,
+            ))
+            .id();
+        let base = world
+            .spawn((
                 SpawnCircleBase,
                 NotShadowCaster,
                 NotShadowReceiver,
                 Mesh3d(spawn_circle_mesh_plane),
-                MeshMaterial3d(material),
+                MeshMaterial3d(material.clone()),
                 Transform::default()
-                    .with_scale(Vec3::new(0.8, 1., 0.8))
-            )],
-        ));
+                    .with_scale(Vec3::new(0.8, 1., 0.8)),
+                ChildOf(circle),
+            ))
+            .id();
+        world
+            .entity_mut(circle)
+            .insert(SpawnCircleParts { base, material });
     }
 }
 
@@ -0,0 +1,199 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use bevy::prelude::*;
+
+use super::{InitSpawnCircle, SpawnCircleSpawnTimer};
+use crate::{
+    assets::MyStates, death::Dying, health::Health,
+    player::PlayerCharacter,
+};
+
+pub struct SpawnQueuePlugin;
+
+impl Plugin for SpawnQueuePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpawnBudget>()
+            .init_resource::<SpawnQueue>()
+            .add_systems(
+                FixedUpdate,
+                drain_spawn_queue
+                    .run_if(in_state(MyStates::Next)),
+            );
+    }
+}
+
+/// Upper bounds on how much can be alive at once.
+///
+/// Spawn requests that would exceed the budget
+/// wait in the [`SpawnQueue`] until enemies die.
+#[derive(Resource)]
+pub struct SpawnBudget {
+    /// enemies alive plus enemies still coming
+    /// out of a spawn circle
+    pub max_enemies: usize,
+    /// spawn circles that haven't spawned their
+    /// enemy yet
+    pub max_pending_circles: usize,
+    /// how many requests can wait in the queue
+    /// before ambient spawns stop being queued.
+    /// Nothing already queued is dropped.
+    pub max_queued: usize,
+}
+
+impl Default for SpawnBudget {
+    fn default() -> Self {
+        Self {
+            max_enemies: 12,
+            max_pending_circles: 4,
+            max_queued: 16,
+        }
+    }
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+pub enum SpawnPriority {
+    /// ambient spawns, such as `RandomSpawn`
+    Low,
+    #[default]
+    Normal,
+    /// spawns a level needs to be completable
+    High,
+}
+
+struct QueuedSpawn {
+    priority: SpawnPriority,
+    sequence: u64,
+    spawn: InitSpawnCircle,
+}
+
+impl PartialEq for QueuedSpawn {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedSpawn {}
+
+impl PartialOrd for QueuedSpawn {
+    fn partial_cmp(
+        &self,
+        other: &Self,
+    ) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedSpawn {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // highest priority first, then oldest first
+        self.priority.cmp(&other.priority).then_with(|| {
+            other.sequence.cmp(&self.sequence)
+        })
+    }
+}
+
+/// Spawn circles waiting for room in the
+/// [`SpawnBudget`]
+#[derive(Resource, Default)]
+pub struct SpawnQueue {
+    queue: BinaryHeap<QueuedSpawn>,
+    next_sequence: u64,
+}
+
+impl SpawnQueue {
+    pub fn push(
+        &mut self,
+        priority: SpawnPriority,
+        spawn: InitSpawnCircle,
+    ) {
+        self.queue.push(QueuedSpawn {
+            priority,
+            sequence: self.next_sequence,
+            spawn,
+        });
+        self.next_sequence += 1;
+    }
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+}
+
+fn drain_spawn_queue(
+    mut spawn_queue: ResMut<SpawnQueue>,
+    budget: Res<SpawnBudget>,
+    // everything with health that isn't the
+    // player, so pylons and bosses count too
+    enemies: Query<
+        (),
+        (
+            With<Health>,
+            Without<PlayerCharacter>,
+            Without<Dying>,
+        ),
+    >,
+    pending_circles: Query<(), With<SpawnCircleSpawnTimer>>,
+    mut commands: Commands,
+) {
+    let mut pending = pending_circles.iter().count();
+    // pending circles are enemies that haven't shown
+    // up yet
+    let mut alive = enemies.iter().count() + pending;
+
+    while alive < budget.max_enemies
+        && pending < budget.max_pending_circles
+    {
+        let Some(next) = spawn_queue.queue.pop() else {
+            break;
+        };
+        commands.queue(next.spawn);
+        alive += 1;
+        pending += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pops_highest_priority_then_oldest() {
+        let mut world = World::new();
+        let event =
+            world.register_system(|_: In<Transform>| {});
+        let spawn = |x: f32| InitSpawnCircle {
+            position: Vec2::new(x, 0.),
+            event,
+            spawn_color: LinearRgba::WHITE,
+        };
+
+        let mut queue = SpawnQueue::default();
+        queue.push(SpawnPriority::Low, spawn(0.));
+        queue.push(SpawnPriority::Normal, spawn(1.));
+        queue.push(SpawnPriority::High, spawn(2.));
+        queue.push(SpawnPriority::Normal, spawn(3.));
+        queue.push(SpawnPriority::High, spawn(4.));
+        assert_eq!(queue.len(), 5);
+
+        let order: Vec<f32> =
+            std::iter::from_fn(|| queue.queue.pop())
+                .map(|next| next.spawn.position.x)
+                .collect();
+        assert_eq!(order, [2., 4., 1., 3., 0.]);
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,428 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    enemy::Stun,
+    health::{Attack, DamageType},
+};
+
+/// Most burn stacks an entity can carry at once
+const MAX_BURN_STACKS: u32 = 5;
+
+pub struct StatusPlugin;
+
+/// Timed effects that stack on an entity.
+///
+/// Every entity with `Health` carries a
+/// [`StatusEffects`] stack. Effects arrive
+/// through [`StatusApplied`], which attacks
+/// trigger for each effect in their payload and
+/// auras trigger for whatever they project. How a
+/// new effect combines with one already running
+/// is up to its [`StatusKind`]: burns stack up to
+/// [`MAX_BURN_STACKS`], shields add up, and the
+/// rest keep whichever effect is stronger. An
+/// effect as strong as the running one only
+/// tops up its time.
+///
+/// Burns deal their damage every tick as an
+/// [`Attack`] from whoever applied them, with
+/// [`DamageType::Burn`] so invulnerability
+/// doesn't stop them. Slows and stuns are read by
+/// player and enemy movement, stuns also stop
+/// attacks, and vulnerability and shields are
+/// applied by `on_attack`.
+impl Plugin for StatusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, tick_status_effects)
+            .add_observer(on_status_applied);
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Deserialize,
+)]
+pub enum StatusKind {
+    /// damage per second per stack
+    Burn,
+    /// share of movement speed taken away, 0 to 1
+    Slow,
+    /// can't move or attack
+    Stun,
+    /// share of extra damage taken
+    Vulnerable,
+    /// damage absorbed before the `Shield`
+    /// component
+    Shield,
+}
+
+/// An effect to apply, see [`StatusKind`] for
+/// what `magnitude` means for each
+#[derive(Clone, Copy, Debug)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub magnitude: f32,
+    pub duration: Duration,
+}
+
+impl StatusEffect {
+    pub fn new(
+        kind: StatusKind,
+        magnitude: f32,
+        duration: Duration,
+    ) -> Self {
+        Self {
+            kind,
+            magnitude,
+            duration,
+        }
+    }
+}
+
+/// Apply a [`StatusEffect`] to an entity
+#[derive(EntityEvent)]
+pub struct StatusApplied {
+    pub entity: Entity,
+    /// who applied it, credited with any burn
+    /// damage
+    pub source: Entity,
+    pub effect: StatusEffect,
+}
+
+/// The effects running on an entity
+#[derive(Component, Default, Debug)]
+pub struct StatusEffects(Vec<ActiveEffect>);
+
+#[derive(Debug)]
+struct ActiveEffect {
+    kind: StatusKind,
+    source: Entity,
+    magnitude: f32,
+    stacks: u32,
+    remaining: Timer,
+}
+
+impl ActiveEffect {
+    /// Take on `effect` if it's stronger, or just
+    /// its time if it's as strong and lasts
+    /// longer
+    fn keep_stronger(
+        &mut self,
+        effect: &StatusEffect,
+        source: Entity,
+    ) {
+        if effect.magnitude > self.magnitude {
+            self.magnitude = effect.magnitude;
+            self.source = source;
+            self.remaining = Timer::new(
+                effect.duration,
+                TimerMode::Once,
+            );
+        } else if effect.magnitude == self.magnitude
+            && self.remaining.remaining() < effect.duration
+        {
+            self.source = source;
+            self.remaining = Timer::new(
+                effect.duration,
+                TimerMode::Once,
+            );
+        }
+    }
+}
+
+impl StatusEffects {
+    fn get(
+        &self,
+        kind: StatusKind,
+    ) -> Option<&ActiveEffect> {
+        self.0.iter().find(|effect| effect.kind == kind)
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.get(kind).is_some()
+    }
+
+    /// Time left on an effect, if it's running
+    pub fn remaining(
+        &self,
+        kind: StatusKind,
+    ) -> Option<Duration> {
+        self.get(kind)
+            .map(|effect| effect.remaining.remaining())
+    }
+
+    fn magnitude(&self, kind: StatusKind) -> f32 {
+        self.get(kind)
+            .map(|effect| effect.magnitude)
+            .unwrap_or_default()
+    }
+
+    /// How much of its usual speed the entity
+    /// moves at
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.has(StatusKind::Stun) {
+            return 0.;
+        }
+        (1. - self.magnitude(StatusKind::Slow)).max(0.)
+    }
+
+    pub fn can_attack(&self) -> bool {
+        !self.has(StatusKind::Stun)
+    }
+
+    /// Multiplier on damage taken
+    pub fn damage_multiplier(&self) -> f32 {
+        1. + self.magnitude(StatusKind::Vulnerable)
+    }
+
+    /// Soak up damage with any shield effect,
+    /// returning what gets through
+    pub fn absorb(&mut self, damage: f32) -> f32 {
+        let Some(shield) =
+            self.0.iter_mut().find(|effect| {
+                effect.kind == StatusKind::Shield
+            })
+        else {
+            return damage;
+        };
+        let absorbed = shield.magnitude.min(damage);
+        shield.magnitude -= absorbed;
+        damage - absorbed
+    }
+
+    fn apply(
+        &mut self,
+        effect: &StatusEffect,
+        source: Entity,
+    ) {
+        let Some(active) = self
+            .0
+            .iter_mut()
+            .find(|active| active.kind == effect.kind)
+        else {
+            self.0.push(ActiveEffect {
+                kind: effect.kind,
+                source,
+                magnitude: effect.magnitude,
+                stacks: 1,
+                remaining: Timer::new(
+                    effect.duration,
+                    TimerMode::Once,
+                ),
+            });
+            return;
+        };
+
+        match effect.kind {
+            StatusKind::Burn => {
+                active.stacks = (active.stacks + 1)
+                    .min(MAX_BURN_STACKS);
+                active.keep_stronger(effect, source);
+            }
+            StatusKind::Shield => {
+                active.magnitude += effect.magnitude;
+                if active.remaining.remaining()
+                    < effect.duration
+                {
+                    active.remaining = Timer::new(
+                        effect.duration,
+                        TimerMode::Once,
+                    );
+                }
+            }
+            StatusKind::Slow
+            | StatusKind::Stun
+            | StatusKind::Vulnerable => {
+                active.keep_stronger(effect, source);
+            }
+        }
+    }
+}
+
+fn on_status_applied(
+    applied: On<StatusApplied>,
+    mut statuses: Query<&mut StatusEffects>,
+    mut commands: Commands,
+) {
+    let Ok(mut statuses) = statuses.get_mut(applied.entity)
+    else {
+        return;
+    };
+    statuses.apply(&applied.effect, applied.source);
+
+    // enemies drop what they're doing
+    if applied.effect.kind == StatusKind::Stun {
+        commands.trigger(Stun {
+            entity: applied.entity,
+            duration: applied.effect.duration,
+        });
+    }
+}
+
+fn tick_status_effects(
+    mut query: Query<(Entity, &mut StatusEffects)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut statuses) in &mut query {
+        for effect in &mut statuses.0 {
+            effect.remaining.tick(time.delta());
+            if effect.kind == StatusKind::Burn {
+                commands.trigger(Attack {
+                    attacker: effect.source,
+                    receiver: entity,
+                    strength: effect.magnitude
+                        * effect.stacks as f32
+                        * time.delta_secs(),
+                    damage_type: DamageType::Burn,
+                    effects: vec![],
+                });
+            }
+        }
+        statuses.0.retain(|effect| {
+            !effect.remaining.is_finished()
+                && !(effect.kind == StatusKind::Shield
+                    && effect.magnitude <= 0.)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::health::Health;
+
+    fn effect(
+        kind: StatusKind,
+        magnitude: f32,
+        secs: u64,
+    ) -> StatusEffect {
+        StatusEffect::new(
+            kind,
+            magnitude,
+            Duration::from_secs(secs),
+        )
+    }
+
+    #[test]
+    fn burns_stack_up_to_the_cap() {
+        let mut statuses = StatusEffects::default();
+        for _ in 0..MAX_BURN_STACKS + 2 {
+            statuses.apply(
+                &effect(StatusKind::Burn, 1., 2),
+                Entity::PLACEHOLDER,
+            );
+        }
+        statuses.apply(
+            &effect(StatusKind::Burn, 3., 2),
+            Entity::PLACEHOLDER,
+        );
+
+        let burn = statuses.get(StatusKind::Burn).unwrap();
+        assert_eq!(burn.stacks, MAX_BURN_STACKS);
+        assert_eq!(burn.magnitude, 3.);
+    }
+
+    #[test]
+    fn others_keep_the_stronger_effect() {
+        let mut statuses = StatusEffects::default();
+        let mut slow = |magnitude, secs| {
+            statuses.apply(
+                &effect(StatusKind::Slow, magnitude, secs),
+                Entity::PLACEHOLDER,
+            );
+            (
+                statuses.speed_multiplier(),
+                statuses.remaining(StatusKind::Slow),
+            )
+        };
+        slow(0.5, 1);
+
+        // a weaker slow doesn't stretch a stronger one
+        assert_eq!(
+            slow(0.2, 4),
+            (0.5, Some(Duration::from_secs(1)))
+        );
+        // one as strong tops up the time
+        assert_eq!(
+            slow(0.5, 3),
+            (0.5, Some(Duration::from_secs(3)))
+        );
+        // a stronger one takes over, time and all
+        assert_eq!(
+            slow(0.75, 1),
+            (0.25, Some(Duration::from_secs(1)))
+        );
+    }
+
+    #[test]
+    fn shields_add_up_and_soak_up_damage() {
+        let mut statuses = StatusEffects::default();
+        statuses.apply(
+            &effect(StatusKind::Shield, 5., 2),
+            Entity::PLACEHOLDER,
+        );
+        statuses.apply(
+            &effect(StatusKind::Shield, 3., 2),
+            Entity::PLACEHOLDER,
+        );
+
+        assert_eq!(statuses.absorb(6.), 0.);
+        assert_eq!(statuses.absorb(6.), 4.);
+        assert_eq!(statuses.absorb(6.), 6.);
+    }
+
+    #[test]
+    fn stun_stops_attacks_until_it_expires() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        let burner = world.spawn_empty().id();
+        let entity = world.spawn(Health::new(100.)).id();
+        world.add_observer(
+            move |attack: On<Attack>,
+                  mut healths: Query<&mut Health>| {
+                assert_eq!(attack.attacker, burner);
+                assert_eq!(
+                    attack.damage_type,
+                    DamageType::Burn
+                );
+                healths
+                    .get_mut(attack.receiver)
+                    .unwrap()
+                    .current -= attack.strength;
+            },
+        );
+        let mut statuses =
+            world.get_mut::<StatusEffects>(entity).unwrap();
+        statuses.apply(
+            &effect(StatusKind::Stun, 0., 1),
+            Entity::PLACEHOLDER,
+        );
+        statuses.apply(
+            &effect(StatusKind::Burn, 2., 3),
+            burner,
+        );
+        assert!(!statuses.can_attack());
+        assert_eq!(statuses.speed_multiplier(), 0.);
+
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs(2));
+        world.run_system_once(tick_status_effects).unwrap();
+
+        let statuses =
+            world.get::<StatusEffects>(entity).unwrap();
+        assert!(statuses.can_attack());
+        assert!(statuses.has(StatusKind::Burn));
+        // one tick of burn over the whole delta,
+        // credited to whoever set it
+        assert_eq!(
+            world.get::<Health>(entity).unwrap().current,
+            96.
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use std::time::Duration;
+
+use bevy::{
+    light::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::render_resource::AsBindGroup,
+    shader::ShaderRef,
+};
+
+pub struct TelegraphPlugin;
+
+/// Warnings painted on the ground before an
+/// attack lands.
+///
+/// A [`GroundTelegraph`] draws its
+/// [`TelegraphShape`] flat on the ground and
+/// fills it in over its lead time, then despawns
+/// when the attack it warns about goes off.
+/// Telegraphs belong to whoever is winding up
+/// through [`TelegraphOf`], so they disappear
+/// with them. Every telegraph shares one quad
+/// scaled to its shape, and materials are reused
+/// once their telegraph is gone.
+impl Plugin for TelegraphPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(
+            MaterialPlugin::<TelegraphMaterial>::default(),
+        )
+        .init_resource::<TelegraphAssets>()
+        .add_systems(Update, fill_telegraphs)
+        .add_observer(on_add_ground_telegraph)
+        .add_observer(on_remove_ground_telegraph);
+    }
+}
+
+/// The area an attack will cover. Cones and lines
+/// point along the telegraph's forward direction.
+#[derive(Clone, Copy, Debug)]
+pub enum TelegraphShape {
+    Circle {
+        radius: f32,
+    },
+    Ring {
+        inner: f32,
+        outer: f32,
+    },
+    /// `angle` is the half-angle, in radians
+    Cone {
+        radius: f32,
+        angle: f32,
+    },
+    Line {
+        length: f32,
+        width: f32,
+    },
+}
+
+impl TelegraphShape {
+    /// Half the size of the quad the shape is
+    /// drawn on
+    fn extent(self) -> f32 {
+        match self {
+            TelegraphShape::Circle { radius }
+            | TelegraphShape::Cone { radius, .. } => radius,
+            TelegraphShape::Ring { outer, .. } => outer,
+            TelegraphShape::Line { length, .. } => length,
+        }
+    }
+
+    /// Which shape the shader draws, with its
+    /// one parameter relative to the extent
+    fn uniforms(self) -> (u32, f32) {
+        match self {
+            TelegraphShape::Circle { .. } => (0, 0.),
+            TelegraphShape::Ring { inner, outer } => {
+                (1, inner / outer)
+            }
+            TelegraphShape::Cone { angle, .. } => {
+                (2, angle)
+            }
+            TelegraphShape::Line { length, width } => {
+                (3, width / 2. / length)
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+#[require(
+    Transform,
+    Visibility,
+    NotShadowCaster,
+    NotShadowReceiver
+)]
+pub struct GroundTelegraph {
+    pub shape: TelegraphShape,
+    pub color: LinearRgba,
+    /// time until the attack lands
+    pub lead: Timer,
+}
+
+impl GroundTelegraph {
+    pub fn new(
+        shape: TelegraphShape,
+        lead: Duration,
+        color: impl Into<LinearRgba>,
+    ) -> Self {
+        Self {
+            shape,
+            color: color.into(),
+            lead: Timer::new(lead, TimerMode::Once),
+        }
+    }
+}
+
+/// The entity winding up the attack a telegraph
+/// warns about
+#[derive(Component)]
+#[relationship(relationship_target = Telegraphs)]
+pub struct TelegraphOf(pub Entity);
+
+#[derive(Component)]
+#[relationship_target(relationship = TelegraphOf, linked_spawn)]
+pub struct Telegraphs(Vec<Entity>);
+
+/// The quad every telegraph is drawn on, and
+/// materials left over from finished telegraphs
+#[derive(Resource)]
+struct TelegraphAssets {
+    mesh: Handle<Mesh>,
+    free: Vec<Handle<TelegraphMaterial>>,
+}
+
+impl FromWorld for TelegraphAssets {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            mesh: world.resource_mut::<Assets<Mesh>>().add(
+                Plane3d::default().mesh().size(2., 2.),
+            ),
+            free: Vec::new(),
+        }
+    }
+}
+
+fn on_add_ground_telegraph(
+    added: On<Add, GroundTelegraph>,
+    mut telegraphs: Query<(
+        &GroundTelegraph,
+        &mut Transform,
+    )>,
+    mut assets: ResMut<TelegraphAssets>,
+    mut materials: ResMut<Assets<TelegraphMaterial>>,
+    mut commands: Commands,
+) {
+    let Ok((telegraph, mut transform)) =
+        telegraphs.get_mut(added.entity)
+    else {
+        return;
+    };
+    let extent = telegraph.shape.extent();
+    transform.scale = Vec3::new(extent, 1., extent);
+
+    let (shape, param) = telegraph.shape.uniforms();
+    let material = TelegraphMaterial {
+        progress: 0.,
+        param,
+        shape,
+        color: telegraph.color,
+    };
+    let handle = match assets.free.pop() {
+        Some(handle) => {
+            if let Some(reused) = materials.get_mut(&handle)
+            {
+                *reused = material;
+            }
+            handle
+        }
+        None => materials.add(material),
+    };
+    commands.entity(added.entity).insert((
+        Mesh3d(assets.mesh.clone()),
+        MeshMaterial3d(handle),
+    ));
+}
+
+fn on_remove_ground_telegraph(
+    removed: On<Remove, GroundTelegraph>,
+    telegraphs: Query<&MeshMaterial3d<TelegraphMaterial>>,
+    mut assets: ResMut<TelegraphAssets>,
+) {
+    if let Ok(material) = telegraphs.get(removed.entity) {
+        assets.free.push(material.0.clone());
+    }
+}
+
+fn fill_telegraphs(
+    mut telegraphs: Query<(
+        Entity,
+        &mut GroundTelegraph,
+        &MeshMaterial3d<TelegraphMaterial>,
+    )>,
+    mut materials: ResMut<Assets<TelegraphMaterial>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut telegraph, material) in &mut telegraphs
+    {
+        telegraph.lead.tick(time.delta());
+        if telegraph.lead.is_finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if let Some(material) = materials.get_mut(material)
+        {
+            material.progress = telegraph.lead.fraction();
+        }
+    }
+}
+
+#[derive(
+    Asset, TypePath, AsBindGroup, Debug, Clone, Default,
+)]
+pub struct TelegraphMaterial {
+    /// how much of the shape is filled in, 0 to 1
+    #[uniform(0)]
+    progress: f32,
+    /// ring thickness, cone half-angle or line
+    /// half-width, depending on the shape
+    #[uniform(0)]
+    param: f32,
+    /// circle, ring, cone or line
+    #[uniform(0)]
+    shape: u32,
+    #[uniform(0)]
+    color: LinearRgba,
+}
+
+impl Material for TelegraphMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/telegraph.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Blend
+    }
+}